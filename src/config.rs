@@ -4,50 +4,115 @@ use serde::Deserialize;
 use std::{fs::File, io::Read, path::Path, time::Duration};
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Camden {
   pub map_win_multiplier: f64,
+  // takeoffs and landings are kept on airport boards that long
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub movements_max_age: Duration,
 }
 
 impl Default for Camden {
   fn default() -> Self {
     Self {
       map_win_multiplier: 1.3,
+      movements_max_age: Duration::from_secs(7200),
     }
   }
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Cache {
+  pub data: String,
+  pub boundaries: String,
   pub runways: String,
   pub geonames_countries: String,
   pub geonames_shapes: String,
+  pub tracon_boundaries: String,
+  // cached files older than these are revalidated before use
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub data_max_age: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub boundaries_max_age: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub runways_max_age: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub geonames_countries_max_age: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub geonames_shapes_max_age: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub tracon_boundaries_max_age: Duration,
 }
 
 impl Default for Cache {
   fn default() -> Self {
     Self {
+      data: "/tmp/vatspy.dat.cache".to_owned(),
+      boundaries: "/tmp/boundaries.geojson.cache".to_owned(),
       runways: "/tmp/runways.csv.cache".to_owned(),
       geonames_countries: "/tmp/geonames.countries.csv.cache".to_owned(),
       geonames_shapes: "/tmp/geonames.shapes.json.zip".to_owned(),
+      tracon_boundaries: "/tmp/tracon-boundaries.geojson.cache".to_owned(),
+      data_max_age: Duration::from_secs(86400),
+      boundaries_max_age: Duration::from_secs(86400),
+      runways_max_age: Duration::from_secs(86400 * 7),
+      geonames_countries_max_age: Duration::from_secs(86400 * 30),
+      geonames_shapes_max_age: Duration::from_secs(86400 * 30),
+      tracon_boundaries_max_age: Duration::from_secs(86400),
     }
   }
 }
 
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedSourceType {
+  Http,
+  File,
+  Replay,
+}
+
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Api {
+  pub source: FeedSourceType,
   pub url: String,
+  // a file for the "file" source, a directory of snapshots for "replay"
+  pub path: String,
   #[serde(deserialize_with = "deserialize_duration")]
   pub poll_period: Duration,
+  // whole request timeout for the http source
   #[serde(deserialize_with = "deserialize_duration")]
   pub timeout: Duration,
+  // failed fetches are retried starting with retry_delay, doubling it on
+  // every consecutive failure up to max_retry_delay
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub retry_delay: Duration,
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub max_retry_delay: Duration,
+  // the feed is reported unhealthy after this many consecutive failures
+  pub max_failures: u32,
+  // or when general.updated_at hasn't advanced for this long
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub stale_after: Duration,
+  // 1.0 replays snapshots in real time, 10.0 is ten times faster,
+  // it must be positive
+  pub replay_speed: f64,
 }
 
 impl Default for Api {
   fn default() -> Self {
     Self {
+      source: FeedSourceType::Http,
       url: "https://data.vatsim.net/v3/vatsim-data.json".to_owned(),
+      path: "".to_owned(),
       poll_period: Duration::from_secs(15),
-      timeout: Duration::from_secs(1),
+      timeout: Duration::from_secs(10),
+      retry_delay: Duration::from_secs(1),
+      max_retry_delay: Duration::from_secs(60),
+      max_failures: 3,
+      stale_after: Duration::from_secs(120),
+      replay_speed: 1.0,
     }
   }
 }
@@ -66,23 +131,41 @@ impl Default for Log {
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Web {
   pub port: u16,
+  // /api/admin endpoints require "Authorization: Bearer <token>",
+  // they're disabled if no token is set
+  pub admin_token: Option<String>,
 }
 
 impl Default for Web {
   fn default() -> Self {
-    Self { port: 8000 }
+    Self {
+      port: 8000,
+      admin_token: None,
+    }
   }
 }
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Fixed {
   pub data_url: String,
   pub boundaries_url: String,
   pub runways_url: String,
   pub geonames_countries_url: String,
   pub geonames_shapes_url: String,
+  // SimAware TRACON boundaries, approach sectors aren't loaded if not set
+  pub tracon_boundaries_url: Option<String>,
+  // file with regex rules mapping controller callsigns to positions
+  pub callsign_rules: Option<String>,
+  // if set, fixed data files are read from this directory instead,
+  // named as in the urls above, i.e. VATSpy.dat or runways.csv
+  pub data_dir: Option<String>,
+  // fixed data is reloaded this often, 0s disables reloading
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub reload_period: Duration,
 }
 
 impl Default for Fixed {
@@ -94,22 +177,87 @@ impl Default for Fixed {
       boundaries_url: "https://raw.githubusercontent.com/vatsimnetwork/vatspy-data-project/master/Boundaries.geojson".to_owned(),
       runways_url: "https://ourairports.com/data/runways.csv".to_owned(),
       geonames_countries_url: "http://download.geonames.org/export/dump/countryInfo.txt".to_owned(),
-      geonames_shapes_url: "http://download.geonames.org/export/dump/shapes_simplified_low.json.zip".to_owned()
+      geonames_shapes_url: "http://download.geonames.org/export/dump/shapes_simplified_low.json.zip".to_owned(),
+      tracon_boundaries_url: None,
+      callsign_rules: None,
+      data_dir: None,
+      reload_period: Duration::from_secs(86400),
     }
   }
 }
 
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackBackend {
+  Mongo,
+  Sqlite,
+  Memory,
+}
+
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Track {
+  pub backend: TrackBackend,
+  // mongo settings
   pub uri: String,
   pub dbname: String,
+  // sqlite settings
+  pub path: String,
+  // tracks older than this are dropped
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub retention: Duration,
+  // change detection: a point is stored only if the pilot has moved
+  // or changed state since the previous stored point of the track
+  pub min_distance_nm: f64,
+  pub min_alt_delta: i32,
+  pub min_hdg_delta: i16,
+  pub min_gs_delta: i32,
+  // points arriving sooner than this are skipped regardless of changes
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub min_interval: Duration,
+  // points are stored at least this often even if nothing has changed
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub max_interval: Duration,
 }
 
 impl Default for Track {
   fn default() -> Self {
     Self {
+      backend: TrackBackend::Mongo,
       uri: "mongodb://localhost:27017".to_owned(),
       dbname: "camden-dev".to_owned(),
+      path: "/tmp/camden-tracks.sqlite".to_owned(),
+      retention: Duration::from_secs(2 * 86400),
+      min_distance_nm: 0.05,
+      min_alt_delta: 100,
+      min_hdg_delta: 5,
+      min_gs_delta: 10,
+      min_interval: Duration::from_secs(0),
+      max_interval: Duration::from_secs(300),
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Recorder {
+  pub enabled: bool,
+  pub dir: String,
+  // snapshots older than max_age are dropped as well as the oldest
+  // ones exceeding max_files
+  #[serde(deserialize_with = "deserialize_duration")]
+  pub max_age: Duration,
+  pub max_files: usize,
+}
+
+impl Default for Recorder {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      dir: "/tmp/camden-feed".to_owned(),
+      max_age: Duration::from_secs(86400),
+      // a day worth of snapshots with the default poll period
+      max_files: 5760,
     }
   }
 }
@@ -121,6 +269,7 @@ pub struct Config {
   pub api: Api,
   pub fixed: Fixed,
   pub track: Track,
+  pub recorder: Recorder,
   pub cache: Cache,
   pub camden: Camden,
 }
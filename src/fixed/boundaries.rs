@@ -1,8 +1,15 @@
-use super::types::Boundaries;
-use crate::types::Point;
+use super::{cached_loader, types::Boundaries};
+use crate::{config::Config, types::Point};
+use geo::BooleanOps;
+use geo_types::{Coord, LineString, MultiPolygon, Polygon};
 use geojson::{Feature, FeatureCollection, GeoJson};
-use log::error;
-use std::{collections::HashMap, error::Error};
+use log::{error, warn};
+use std::{
+  collections::HashMap,
+  error::Error,
+  io::Read,
+  panic::{self, AssertUnwindSafe},
+};
 
 fn lng_less(a: f64, b: f64) -> bool {
   let d1 = (b - a).rem_euclid(360.0);
@@ -21,57 +28,46 @@ fn lng_center(min: f64, max: f64) -> f64 {
   }
 }
 
-fn extract_boundaries(feat: &Feature) -> Option<Boundaries> {
-  let props = &feat.properties;
-  let geom = feat.geometry.as_ref()?;
-  if let Some(props) = props {
-    let id = props.get("id")?.as_str()?.to_owned();
-    let is_oceanic = props.get("oceanic")?.as_str()? == "1";
-    let region = props.get("region")?.as_str()?.to_owned();
-    let division = props.get("division")?.as_str()?.to_owned();
-    let mut points = vec![];
+/// Polygons of a GeoJSON geometry along with their bounding box, min.lng
+/// is greater than max.lng for shapes crossing the antimeridian
+pub(super) struct Geometry {
+  pub min: Point,
+  pub max: Point,
+  pub center: Point,
+  pub points: Vec<Vec<Point>>,
+}
+
+impl Geometry {
+  pub fn from_points(points: Vec<Vec<Point>>) -> Self {
     let mut min_lng = 0.0;
     let mut max_lng = 0.0;
     let mut min_lat = 0.0;
     let mut max_lat = 0.0;
     let mut minmax_initialised = false;
-    match &geom.value {
-      geojson::Value::MultiPolygon(mpoly) => {
-        for poly in mpoly {
-          let mut ppoly = vec![];
-          for inner in poly {
-            for inner in inner {
-              let (lng, lat) = (inner[0], inner[1]);
-
-              if minmax_initialised {
-                if min_lat > lat {
-                  min_lat = lat;
-                }
-                if max_lat < lat {
-                  max_lat = lat;
-                }
-                if lng_less(max_lng, lng) {
-                  max_lng = lng;
-                }
-                if lng_less(lng, min_lng) {
-                  min_lng = lng;
-                }
-              } else {
-                min_lat = lat;
-                max_lat = lat;
-                min_lng = lng;
-                max_lng = lng;
-                minmax_initialised = true;
-              }
-
-              ppoly.push(Point { lat, lng });
-            }
-          }
-          points.push(ppoly)
+    for point in points.iter().flatten() {
+      let (lng, lat) = (point.lng, point.lat);
+
+      if minmax_initialised {
+        if min_lat > lat {
+          min_lat = lat;
+        }
+        if max_lat < lat {
+          max_lat = lat;
         }
+        if lng_less(max_lng, lng) {
+          max_lng = lng;
+        }
+        if lng_less(lng, min_lng) {
+          min_lng = lng;
+        }
+      } else {
+        min_lat = lat;
+        max_lat = lat;
+        min_lng = lng;
+        max_lng = lng;
+        minmax_initialised = true;
       }
-      _ => return None,
-    };
+    }
 
     let min = Point {
       lat: min_lat,
@@ -90,15 +86,117 @@ fn extract_boundaries(feat: &Feature) -> Option<Boundaries> {
       lng: center_lng,
     };
 
+    Self {
+      min,
+      max,
+      center,
+      points,
+    }
+  }
+}
+
+pub(super) fn extract_geometry(value: &geojson::Value) -> Option<Geometry> {
+  let polys = match value {
+    geojson::Value::MultiPolygon(mpoly) => mpoly.iter().collect::<Vec<_>>(),
+    geojson::Value::Polygon(poly) => vec![poly],
+    _ => return None,
+  };
+
+  let mut points = vec![];
+  for poly in polys {
+    let mut ppoly = vec![];
+    for inner in poly {
+      for inner in inner {
+        let (lng, lat) = (inner[0], inner[1]);
+        ppoly.push(Point { lat, lng });
+      }
+    }
+    points.push(ppoly)
+  }
+  Some(Geometry::from_points(points))
+}
+
+fn union(polys: Vec<Polygon>) -> MultiPolygon {
+  polys
+    .into_iter()
+    .fold(MultiPolygon::new(vec![]), |acc, poly| {
+      acc.union(&MultiPolygon::new(vec![poly]))
+    })
+}
+
+/// Merges polygons into a single outline, i.e. member FIRs into their UIR.
+/// Shared borders disappear while holes are dropped. If any of the
+/// polygons crosses the antimeridian, all of them are unwrapped to 0..360
+/// longitudes for the time of merging.
+pub(super) fn merge_polygons(rings: &[&Vec<Point>]) -> Geometry {
+  let rings: Vec<&Vec<Point>> = rings.iter().copied().filter(|r| r.len() >= 3).collect();
+  let unwrap = rings.iter().any(|ring| {
+    ring
+      .windows(2)
+      .any(|pair| (pair[0].lng - pair[1].lng).abs() > 180.0)
+  });
+  let polys: Vec<Polygon> = rings
+    .iter()
+    .map(|ring| {
+      let coords: Vec<Coord> = ring
+        .iter()
+        .map(|p| Coord {
+          x: if unwrap && p.lng < 0.0 {
+            p.lng + 360.0
+          } else {
+            p.lng
+          },
+          y: p.lat,
+        })
+        .collect();
+      Polygon::new(LineString::from(coords), vec![])
+    })
+    .collect();
+
+  // boolean ops may panic on degenerate input, the polygons are
+  // kept as is in that case
+  let merged = panic::catch_unwind(AssertUnwindSafe(|| union(polys)));
+  let points = match merged {
+    Ok(merged) => merged
+      .into_iter()
+      .map(|poly| {
+        poly
+          .exterior()
+          .coords()
+          .map(|c| Point {
+            lat: c.y,
+            lng: if c.x > 180.0 { c.x - 360.0 } else { c.x },
+          })
+          .collect()
+      })
+      .collect(),
+    Err(_) => {
+      warn!("error merging polygons, using them as is");
+      rings.into_iter().cloned().collect()
+    }
+  };
+  Geometry::from_points(points)
+}
+
+fn extract_boundaries(feat: &Feature) -> Option<Boundaries> {
+  let props = &feat.properties;
+  let geom = feat.geometry.as_ref()?;
+  if let Some(props) = props {
+    let id = props.get("id")?.as_str()?.to_owned();
+    let is_oceanic = props.get("oceanic")?.as_str()? == "1";
+    let region = props.get("region")?.as_str()?.to_owned();
+    let division = props.get("division")?.as_str()?.to_owned();
+    let geometry = extract_geometry(&geom.value)?;
+
     Some(Boundaries {
       id,
       region,
       division,
       is_oceanic,
-      min,
-      max,
-      center,
-      points,
+      min: geometry.min,
+      max: geometry.max,
+      center: geometry.center,
+      points: geometry.points,
     })
   } else {
     error!("no props found in feature {:?}", feat);
@@ -106,8 +204,20 @@ fn extract_boundaries(feat: &Feature) -> Option<Boundaries> {
   }
 }
 
-pub async fn load_boundaries(url: &str) -> Result<HashMap<String, Boundaries>, Box<dyn Error>> {
-  let raw_geojson = reqwest::get(url).await?.text().await?;
+pub async fn load_boundaries(
+  cfg: &Config,
+  refresh: bool,
+) -> Result<HashMap<String, Boundaries>, Box<dyn Error>> {
+  let mut cache_file = cached_loader(
+    &cfg.fixed,
+    &cfg.fixed.boundaries_url,
+    &cfg.cache.boundaries,
+    cfg.cache.boundaries_max_age,
+    refresh,
+  )
+  .await?;
+  let mut raw_geojson = String::new();
+  cache_file.read_to_string(&mut raw_geojson)?;
   let geo = raw_geojson.parse::<GeoJson>()?;
   let coll = FeatureCollection::try_from(geo)?;
   let mut res = HashMap::new();
@@ -122,7 +232,24 @@ pub async fn load_boundaries(url: &str) -> Result<HashMap<String, Boundaries>, B
 
 #[cfg(test)]
 mod test {
-  use super::lng_less;
+  use super::{lng_less, merge_polygons};
+  use crate::types::Point;
+
+  fn square(min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) -> Vec<Point> {
+    [
+      (min_lng, min_lat),
+      (max_lng, min_lat),
+      (max_lng, max_lat),
+      (min_lng, max_lat),
+      (min_lng, min_lat),
+    ]
+    .iter()
+    .map(|(lng, lat)| Point {
+      lat: *lat,
+      lng: *lng,
+    })
+    .collect()
+  }
 
   #[test]
   fn test_lng_less() {
@@ -130,4 +257,32 @@ mod test {
     assert!(lng_less(-10.0, 10.0));
     assert!(lng_less(170.0, -150.0))
   }
+
+  #[test]
+  fn test_merge_polygons() {
+    let west = square(0.0, 0.0, 10.0, 10.0);
+    let east = square(10.0, 0.0, 20.0, 10.0);
+    let merged = merge_polygons(&[&west, &east]);
+    assert_eq!(merged.points.len(), 1);
+    assert_eq!((merged.min.lng, merged.min.lat), (0.0, 0.0));
+    assert_eq!((merged.max.lng, merged.max.lat), (20.0, 10.0));
+    assert_eq!(merged.center.lng, 10.0);
+
+    // disjoint polygons stay apart
+    let far = square(30.0, 0.0, 40.0, 10.0);
+    let merged = merge_polygons(&[&west, &far]);
+    assert_eq!(merged.points.len(), 2);
+
+    // FIRs on both sides of the antimeridian
+    let west = square(170.0, -40.0, 180.0, -20.0);
+    let east = square(-180.0, -40.0, -170.0, -20.0);
+    let crossing = square(175.0, -20.0, -175.0, -10.0);
+    let merged = merge_polygons(&[&west, &east, &crossing]);
+    assert_eq!(merged.points.len(), 1);
+    assert_eq!(merged.min.lng, 170.0);
+    assert_eq!(merged.max.lng, -170.0);
+    assert!(merged.points[0]
+      .iter()
+      .all(|p| p.lng >= -180.0 && p.lng <= 180.0));
+  }
 }
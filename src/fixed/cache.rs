@@ -0,0 +1,121 @@
+/// Fixed data cache metadata
+/// Every cached file has a <file>.meta sidecar keeping the source url, fetch
+/// time, HTTP validators and a checksum of the contents, so outdated files
+/// get revalidated and truncated or corrupted ones are never used.
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+  ffi::OsString,
+  fs, io,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheMeta {
+  pub url: String,
+  pub fetched_at: DateTime<Utc>,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+  pub sha256: String,
+}
+
+pub fn checksum(data: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(data))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut path = OsString::from(path.as_os_str());
+  path.push(suffix);
+  path.into()
+}
+
+/// Writes data to a temporary file first and then renames it
+/// so a partially written file is never seen under the path
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+  let tmp_path = with_suffix(path, ".tmp");
+  fs::write(&tmp_path, data)?;
+  fs::rename(&tmp_path, path)
+}
+
+impl CacheMeta {
+  pub fn new(url: &str, data: &[u8], etag: Option<String>, last_modified: Option<String>) -> Self {
+    Self {
+      url: url.to_owned(),
+      fetched_at: Utc::now(),
+      etag,
+      last_modified,
+      sha256: checksum(data),
+    }
+  }
+
+  /// Loads metadata of a cached file. None is returned if there's no file
+  /// or metadata, or the file was fetched from another url, or its contents
+  /// don't match the checksum.
+  pub fn load(path: &Path, url: &str) -> Option<Self> {
+    let raw = fs::read(with_suffix(path, ".meta")).ok()?;
+    let meta: Self = serde_json::from_slice(&raw).ok()?;
+    if meta.url != url {
+      return None;
+    }
+    let data = fs::read(path).ok()?;
+    if checksum(&data) != meta.sha256 {
+      warn!("{} checksum mismatch, ignoring the file", path.display());
+      return None;
+    }
+    Some(meta)
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let raw = serde_json::to_vec_pretty(self)?;
+    write_atomic(&with_suffix(path, ".meta"), &raw)
+  }
+
+  pub fn is_fresh(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+    match chrono::Duration::from_std(max_age) {
+      Ok(max_age) => now - self.fetched_at < max_age,
+      // too large to be represented, never expires
+      Err(_) => true,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env::temp_dir;
+  use uuid::Uuid;
+
+  #[test]
+  fn test_cache_meta() {
+    let dir = temp_dir().join(format!("camden-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("runways.csv.cache");
+    let url = "https://ourairports.com/data/runways.csv";
+
+    write_atomic(&path, b"runways").unwrap();
+    assert!(!with_suffix(&path, ".tmp").exists());
+    // no metadata for files cached before
+    assert_eq!(CacheMeta::load(&path, url), None);
+
+    let meta = CacheMeta::new(url, b"runways", Some("\"abc\"".into()), None);
+    meta.save(&path).unwrap();
+    assert_eq!(CacheMeta::load(&path, url), Some(meta.clone()));
+    assert_eq!(
+      CacheMeta::load(&path, "https://example.com/runways.csv"),
+      None
+    );
+
+    let day = Duration::from_secs(86400);
+    assert!(meta.is_fresh(day, Utc::now()));
+    assert!(!meta.is_fresh(day, Utc::now() + chrono::Duration::days(2)));
+
+    // truncated file
+    fs::write(&path, b"runw").unwrap();
+    assert_eq!(CacheMeta::load(&path, url), None);
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
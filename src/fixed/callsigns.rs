@@ -0,0 +1,195 @@
+/// Callsign mapping rules
+/// Controllers are attached to airports, FIRs and TRACONs by the callsign
+/// part before the first underscore which doesn't work for callsigns like
+/// LON_S_CTR. Rules map callsigns matching a regex to a position explicitly,
+/// the first matching rule wins.
+use regex::Regex;
+use serde::Deserialize;
+use std::{error::Error, fmt::Display, fs, path::Path};
+
+#[derive(Debug)]
+pub enum RulesError {
+  IOError(std::io::Error),
+  ParseError(toml::de::Error),
+  InvalidPattern(String, regex::Error),
+  InvalidTarget(String),
+}
+
+impl Display for RulesError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RulesError::IOError(err) => write!(f, "error reading callsign rules: {err}"),
+      RulesError::ParseError(err) => write!(f, "error parsing callsign rules: {err}"),
+      RulesError::InvalidPattern(pattern, err) => {
+        write!(f, "invalid callsign pattern \"{pattern}\": {err}")
+      }
+      RulesError::InvalidTarget(pattern) => write!(
+        f,
+        "callsign rule \"{pattern}\" must have exactly one of airport, fir or tracon"
+      ),
+    }
+  }
+}
+
+impl Error for RulesError {}
+
+impl From<std::io::Error> for RulesError {
+  fn from(err: std::io::Error) -> Self {
+    Self::IOError(err)
+  }
+}
+
+impl From<toml::de::Error> for RulesError {
+  fn from(err: toml::de::Error) -> Self {
+    Self::ParseError(err)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Position {
+  Airport(String),
+  FIR(String),
+  TRACON(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRule {
+  pattern: String,
+  airport: Option<String>,
+  fir: Option<String>,
+  tracon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRules {
+  #[serde(default)]
+  rule: Vec<RawRule>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallsignRules {
+  rules: Vec<(Regex, Position)>,
+}
+
+impl CallsignRules {
+  pub fn empty() -> Self {
+    Self::default()
+  }
+
+  /// Parses rules like
+  ///
+  /// [[rule]]
+  /// pattern = "LON_[A-Z]*_?CTR"
+  /// fir = "EGTT"
+  ///
+  /// patterns must match the whole callsign
+  pub fn parse(src: &str) -> Result<Self, RulesError> {
+    let raw: RawRules = toml::from_str(src)?;
+    let mut rules = vec![];
+    for rule in raw.rule {
+      let re = Regex::new(&format!("^(?:{})$", rule.pattern))
+        .map_err(|err| RulesError::InvalidPattern(rule.pattern.clone(), err))?;
+      let position = match (rule.airport, rule.fir, rule.tracon) {
+        (Some(code), None, None) => Position::Airport(code),
+        (None, Some(code), None) => Position::FIR(code),
+        (None, None, Some(code)) => Position::TRACON(code),
+        _ => return Err(RulesError::InvalidTarget(rule.pattern)),
+      };
+      rules.push((re, position));
+    }
+    Ok(Self { rules })
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, RulesError> {
+    let src = fs::read_to_string(path)?;
+    Self::parse(&src)
+  }
+
+  pub fn len(&self) -> usize {
+    self.rules.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  fn find<F>(&self, callsign: &str, f: F) -> Option<&str>
+  where
+    F: Fn(&Position) -> Option<&str>,
+  {
+    self
+      .rules
+      .iter()
+      .filter(|(re, _)| re.is_match(callsign))
+      .find_map(|(_, position)| f(position))
+  }
+
+  pub fn airport(&self, callsign: &str) -> Option<&str> {
+    self.find(callsign, |position| match position {
+      Position::Airport(code) => Some(code),
+      _ => None,
+    })
+  }
+
+  pub fn fir(&self, callsign: &str) -> Option<&str> {
+    self.find(callsign, |position| match position {
+      Position::FIR(code) => Some(code),
+      _ => None,
+    })
+  }
+
+  pub fn tracon(&self, callsign: &str) -> Option<&str> {
+    self.find(callsign, |position| match position {
+      Position::TRACON(code) => Some(code),
+      _ => None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEST_RULES: &str = r#"
+[[rule]]
+pattern = "LON_[A-Z]*_?CTR"
+fir = "EGTT"
+
+[[rule]]
+pattern = "EDDF_.*DEP"
+airport = "EDDF"
+
+[[rule]]
+pattern = "NY_.*APP"
+tracon = "N90"
+
+[[rule]]
+pattern = "NY_.*APP"
+airport = "KJFK"
+"#;
+
+  #[test]
+  fn test_rules() {
+    let rules = CallsignRules::parse(TEST_RULES).unwrap();
+    assert_eq!(rules.len(), 4);
+    assert_eq!(rules.fir("LON_S_CTR"), Some("EGTT"));
+    assert_eq!(rules.fir("LON_CTR"), Some("EGTT"));
+    // patterns match whole callsigns
+    assert_eq!(rules.fir("XLON_S_CTR"), None);
+    assert_eq!(rules.airport("LON_S_CTR"), None);
+    assert_eq!(rules.airport("EDDF_N_DEP"), Some("EDDF"));
+    assert_eq!(rules.tracon("NY_CAM_APP"), Some("N90"));
+    assert_eq!(rules.airport("NY_CAM_APP"), Some("KJFK"));
+  }
+
+  #[test]
+  fn test_invalid_rules() {
+    let res = CallsignRules::parse("[[rule]]\npattern = \"LON_(\"\nfir = \"EGTT\"\n");
+    assert!(matches!(res, Err(RulesError::InvalidPattern(_, _))));
+    let res = CallsignRules::parse("[[rule]]\npattern = \"LON_CTR\"\n");
+    assert!(matches!(res, Err(RulesError::InvalidTarget(_))));
+    let res =
+      CallsignRules::parse("[[rule]]\npattern = \"LON_CTR\"\nfir = \"EGTT\"\nairport = \"EGLL\"\n");
+    assert!(matches!(res, Err(RulesError::InvalidTarget(_))));
+  }
+}
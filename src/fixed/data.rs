@@ -1,6 +1,9 @@
 use super::{
+  boundaries::merge_polygons,
+  callsigns::CallsignRules,
   geonames::Geonames,
-  types::{Airport, Country, GeonamesCountry, FIR, UIR},
+  types::{Airport, Country, FIRShape, GeonamesCountry, FIR, TRACON, UIR},
+  validate::ValidationReport,
 };
 use crate::{
   moving::controller::{Controller, Facility},
@@ -8,7 +11,8 @@ use crate::{
   weather::WeatherInfo,
 };
 use log::error;
-use std::collections::HashMap;
+use rstar::{RTree, AABB};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct FixedData {
@@ -16,6 +20,7 @@ pub struct FixedData {
   airports: Vec<Airport>,
   firs: Vec<FIR>,
   uirs: Vec<UIR>,
+  tracons: Vec<TRACON>,
   arpt_icao_idx: HashMap<String, Vec<usize>>,
   arpt_iata_idx: HashMap<String, usize>,
   arpt_compound_idx: HashMap<String, usize>,
@@ -23,7 +28,14 @@ pub struct FixedData {
   firs_icao_idx: HashMap<String, usize>,
   firs_prefix_idx: HashMap<String, usize>,
   uirs_idx: HashMap<String, usize>,
+  // UIRs the FIR is a member of
+  fir_uirs_idx: HashMap<String, Vec<usize>>,
+  tracons_key_idx: HashMap<String, usize>,
+  tracons_prefix_idx: HashMap<String, Vec<usize>>,
   geonames: Geonames,
+  fir_shapes: RTree<FIRShape>,
+  callsign_rules: CallsignRules,
+  validation: ValidationReport,
 }
 
 impl FixedData {
@@ -33,6 +45,7 @@ impl FixedData {
       airports: vec![],
       firs: vec![],
       uirs: vec![],
+      tracons: vec![],
       arpt_icao_idx: HashMap::new(),
       arpt_iata_idx: HashMap::new(),
       arpt_compound_idx: HashMap::new(),
@@ -40,7 +53,13 @@ impl FixedData {
       firs_icao_idx: HashMap::new(),
       firs_prefix_idx: HashMap::new(),
       uirs_idx: HashMap::new(),
+      fir_uirs_idx: HashMap::new(),
+      tracons_key_idx: HashMap::new(),
+      tracons_prefix_idx: HashMap::new(),
       geonames: Geonames::empty(),
+      fir_shapes: RTree::new(),
+      callsign_rules: CallsignRules::empty(),
+      validation: ValidationReport::default(),
     }
   }
 
@@ -49,6 +68,7 @@ impl FixedData {
     self.airports = other.airports;
     self.firs = other.firs;
     self.uirs = other.uirs;
+    self.tracons = other.tracons;
     self.arpt_icao_idx = other.arpt_icao_idx;
     self.arpt_iata_idx = other.arpt_iata_idx;
     self.arpt_compound_idx = other.arpt_compound_idx;
@@ -56,15 +76,23 @@ impl FixedData {
     self.firs_icao_idx = other.firs_icao_idx;
     self.firs_prefix_idx = other.firs_prefix_idx;
     self.uirs_idx = other.uirs_idx;
+    self.fir_uirs_idx = other.fir_uirs_idx;
+    self.tracons_key_idx = other.tracons_key_idx;
+    self.tracons_prefix_idx = other.tracons_prefix_idx;
     self.geonames = other.geonames;
+    self.fir_shapes = other.fir_shapes;
+    self.callsign_rules = other.callsign_rules;
+    self.validation = other.validation;
   }
 
   pub fn new(
     countries: Vec<Country>,
     airports: Vec<Airport>,
     firs: Vec<FIR>,
-    uirs: Vec<UIR>,
+    mut uirs: Vec<UIR>,
+    tracons: Vec<TRACON>,
     geonames: Geonames,
+    validation: ValidationReport,
   ) -> Self {
     let mut arpt_icao_idx: HashMap<String, Vec<usize>> = HashMap::new();
     let mut arpt_iata_idx: HashMap<String, usize> = HashMap::new();
@@ -97,9 +125,55 @@ impl FixedData {
       firs_prefix_idx.insert(fir.prefix.clone(), idx);
     }
 
+    let mut shapes = vec![];
+    for (idx, fir) in firs.iter().enumerate() {
+      for ring in fir.boundaries.points.iter() {
+        shapes.extend(FIRShape::new(idx, ring));
+      }
+    }
+    let fir_shapes = RTree::bulk_load(shapes);
+
     let mut uirs_idx = HashMap::new();
-    for (idx, uir) in uirs.iter().enumerate() {
+    let mut fir_uirs_idx: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, uir) in uirs.iter_mut().enumerate() {
       uirs_idx.insert(uir.icao.clone(), idx);
+      let members: Vec<&FIR> = uir
+        .fir_ids
+        .iter()
+        .filter_map(|fir_id| {
+          firs_icao_idx
+            .get(fir_id)
+            .or_else(|| firs_prefix_idx.get(fir_id))
+        })
+        .map(|fir_idx| &firs[*fir_idx])
+        .collect();
+      for fir in members.iter() {
+        fir_uirs_idx.entry(fir.icao.clone()).or_default().push(idx);
+      }
+      let rings: Vec<&Vec<Point>> = members
+        .iter()
+        .flat_map(|fir| fir.boundaries.points.iter())
+        .collect();
+      if !rings.is_empty() {
+        let geometry = merge_polygons(&rings);
+        uir.min = geometry.min;
+        uir.max = geometry.max;
+        uir.center = geometry.center;
+        uir.points = geometry.points;
+      }
+      uir.country = members.iter().find_map(|fir| fir.country.clone());
+    }
+
+    let mut tracons_key_idx = HashMap::new();
+    let mut tracons_prefix_idx: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, tracon) in tracons.iter().enumerate() {
+      tracons_key_idx.insert(tracon.key(), idx);
+      for prefix in tracon.prefixes.iter() {
+        tracons_prefix_idx
+          .entry(prefix.clone())
+          .or_default()
+          .push(idx);
+      }
     }
 
     Self {
@@ -107,6 +181,7 @@ impl FixedData {
       airports,
       firs,
       uirs,
+      tracons,
       arpt_icao_idx,
       arpt_iata_idx,
       arpt_compound_idx,
@@ -114,7 +189,13 @@ impl FixedData {
       firs_icao_idx,
       firs_prefix_idx,
       uirs_idx,
+      fir_uirs_idx,
+      tracons_key_idx,
+      tracons_prefix_idx,
       geonames,
+      fir_shapes,
+      callsign_rules: CallsignRules::empty(),
+      validation,
     }
   }
 
@@ -126,6 +207,18 @@ impl FixedData {
     &self.firs
   }
 
+  pub fn uirs(&self) -> &Vec<UIR> {
+    &self.uirs
+  }
+
+  pub fn tracons(&self) -> &Vec<TRACON> {
+    &self.tracons
+  }
+
+  pub fn validation(&self) -> &ValidationReport {
+    &self.validation
+  }
+
   pub fn set_airport_weather(&mut self, icao: &str, wx: WeatherInfo) {
     let idx = self.find_airport_idx(icao);
     if let Some(idx) = idx {
@@ -136,10 +229,29 @@ impl FixedData {
     }
   }
 
+  pub fn set_callsign_rules(&mut self, rules: CallsignRules) {
+    self.callsign_rules = rules;
+  }
+
+  // code of the airport or FIR a controller belongs to, callsign rules
+  // take precedence over the part of the callsign before the first "_"
+  fn airport_code<'a>(&'a self, callsign: &'a str) -> &'a str {
+    self
+      .callsign_rules
+      .airport(callsign)
+      .unwrap_or_else(|| callsign.split('_').next().unwrap_or(callsign))
+  }
+
+  fn fir_code<'a>(&'a self, callsign: &'a str) -> &'a str {
+    self
+      .callsign_rules
+      .fir(callsign)
+      .unwrap_or_else(|| callsign.split('_').next().unwrap_or(callsign))
+  }
+
   pub fn set_airport_controller(&mut self, ctrl: Controller) -> Option<&Airport> {
     let mut ctrl = ctrl;
-    let tokens: Vec<&str> = ctrl.callsign.split('_').collect();
-    let code = tokens[0];
+    let code = self.airport_code(&ctrl.callsign);
     let idx = self.find_airport_idx(code);
     if let Some(idx) = idx {
       let arpt = self.airports.get_mut(idx);
@@ -152,16 +264,10 @@ impl FixedData {
           Facility::Approach => Some(format!("{} Approach", arpt.name)),
           _ => unreachable!(),
         };
-        match &ctrl.facility {
-          Facility::ATIS => {
-            arpt.controllers.atis = Some(ctrl);
-            arpt.set_active_runways();
-          }
-          Facility::Delivery => arpt.controllers.delivery = Some(ctrl),
-          Facility::Ground => arpt.controllers.ground = Some(ctrl),
-          Facility::Tower => arpt.controllers.tower = Some(ctrl),
-          Facility::Approach => arpt.controllers.approach = Some(ctrl),
-          _ => unreachable!(),
+        let is_atis = ctrl.facility == Facility::ATIS;
+        arpt.controllers.insert(ctrl);
+        if is_atis {
+          arpt.set_active_runways();
         }
         return Some(arpt);
       } else {
@@ -177,22 +283,14 @@ impl FixedData {
   }
 
   pub fn reset_airport_controller(&mut self, ctrl: &Controller) {
-    let tokens: Vec<&str> = ctrl.callsign.split('_').collect();
-    let code = tokens[0];
+    let code = self.airport_code(&ctrl.callsign);
     let idx = self.find_airport_idx(code);
     if let Some(idx) = idx {
       let arpt = self.airports.get_mut(idx);
       if let Some(arpt) = arpt {
-        match &ctrl.facility {
-          Facility::ATIS => {
-            arpt.controllers.atis = None;
-            arpt.reset_active_runways();
-          }
-          Facility::Delivery => arpt.controllers.delivery = None,
-          Facility::Ground => arpt.controllers.ground = None,
-          Facility::Tower => arpt.controllers.tower = None,
-          Facility::Approach => arpt.controllers.approach = None,
-          _ => unreachable!(),
+        arpt.controllers.remove(ctrl);
+        if ctrl.facility == Facility::ATIS {
+          arpt.set_active_runways();
         }
       } else {
         error!(
@@ -206,11 +304,11 @@ impl FixedData {
   }
 
   pub fn set_fir_controller(&mut self, ctrl: Controller) -> Option<FIR> {
-    let tokens: Vec<&str> = ctrl.callsign.split('_').collect();
-    let code = tokens[0];
-    let country = self
-      .country_idx
-      .get(&code[..2])
+    let code = self.fir_code(&ctrl.callsign);
+    // codes from callsign rules can be anything, i.e. shorter than a prefix
+    let country = code
+      .get(..2)
+      .and_then(|prefix| self.country_idx.get(prefix))
       .map(|idx| self.countries.get(*idx).unwrap());
 
     let fir_ids = self.find_fir_indices(code);
@@ -239,8 +337,7 @@ impl FixedData {
   }
 
   pub fn reset_fir_controller(&mut self, ctrl: &Controller) {
-    let tokens: Vec<&str> = ctrl.callsign.split('_').collect();
-    let code = tokens[0];
+    let code = self.fir_code(&ctrl.callsign);
     let fir_ids = self.find_fir_indices(code);
     for idx in fir_ids {
       let fir = self.firs.get_mut(idx);
@@ -250,6 +347,60 @@ impl FixedData {
     }
   }
 
+  // TRACONs are matched by the first callsign token, the ones having
+  // a suffix (i.e. DEP) take precedence if the callsign ends with it
+  fn find_tracon_indices(&self, callsign: &str) -> Vec<usize> {
+    if let Some(key) = self.callsign_rules.tracon(callsign) {
+      return self.tracons_key_idx.get(key).copied().into_iter().collect();
+    }
+    let tokens: Vec<&str> = callsign.split('_').collect();
+    let suffix = if tokens.len() > 1 {
+      tokens.last().copied()
+    } else {
+      None
+    };
+    let candidates = match self.tracons_prefix_idx.get(tokens[0]) {
+      Some(candidates) => candidates,
+      None => return vec![],
+    };
+
+    let with_suffix: Vec<usize> = candidates
+      .iter()
+      .copied()
+      .filter(|idx| suffix.is_some() && self.tracons[*idx].suffix.as_deref() == suffix)
+      .collect();
+    if !with_suffix.is_empty() {
+      return with_suffix;
+    }
+    candidates
+      .iter()
+      .copied()
+      .filter(|idx| self.tracons[*idx].suffix.is_none())
+      .collect()
+  }
+
+  pub fn find_tracon(&self, key: &str) -> Option<&TRACON> {
+    let idx = self.tracons_key_idx.get(key)?;
+    self.tracons.get(*idx)
+  }
+
+  pub fn set_tracon_controller(&mut self, ctrl: Controller) -> Option<&TRACON> {
+    let indices = self.find_tracon_indices(&ctrl.callsign);
+    for idx in indices.iter() {
+      let tracon = &mut self.tracons[*idx];
+      let mut ctrl = ctrl.clone();
+      ctrl.human_readable = Some(tracon.name.clone());
+      tracon.controllers.insert(ctrl.callsign.clone(), ctrl);
+    }
+    indices.first().map(|idx| &self.tracons[*idx])
+  }
+
+  pub fn reset_tracon_controller(&mut self, ctrl: &Controller) {
+    for idx in self.find_tracon_indices(&ctrl.callsign) {
+      self.tracons[idx].controllers.remove(&ctrl.callsign);
+    }
+  }
+
   fn find_fir_idx_by_icao(&self, query: &str) -> Option<usize> {
     self.firs_icao_idx.get(query).copied()
   }
@@ -278,24 +429,40 @@ impl FixedData {
       }
     }
 
-    let uir = self.uirs_idx.get(query).map(|idx| &self.uirs[*idx]);
+    vec![]
+  }
 
-    if let Some(uir) = uir {
-      let mut idcs = vec![];
-      for fir_id in uir.fir_ids.iter() {
-        let idx = self
-          .find_fir_idx_by_icao(fir_id)
-          .or_else(|| self.find_fir_idx_by_prefix(fir_id));
-        if let Some(idx) = idx {
-          idcs.push(idx)
-        }
-      }
-      idcs
-    } else {
-      vec![]
+  // UIRs are looked up only if the code doesn't refer to a FIR,
+  // the same way as in find_fir_indices()
+  fn find_uir_idx(&self, code: &str) -> Option<usize> {
+    if !self.find_fir_indices(code).is_empty() {
+      return None;
+    }
+    self.uirs_idx.get(code).copied()
+  }
+
+  pub fn set_uir_controller(&mut self, ctrl: Controller) -> Option<&UIR> {
+    let code = self.fir_code(&ctrl.callsign);
+    let idx = self.find_uir_idx(code)?;
+    let uir = &mut self.uirs[idx];
+    let mut ctrl = ctrl;
+    ctrl.human_readable = Some(uir.name.clone());
+    uir.controllers.insert(ctrl.callsign.clone(), ctrl);
+    Some(uir)
+  }
+
+  pub fn reset_uir_controller(&mut self, ctrl: &Controller) {
+    let code = self.fir_code(&ctrl.callsign);
+    if let Some(idx) = self.find_uir_idx(code) {
+      self.uirs[idx].controllers.remove(&ctrl.callsign);
     }
   }
 
+  pub fn find_uir(&self, icao: &str) -> Option<&UIR> {
+    let idx = self.uirs_idx.get(icao)?;
+    self.uirs.get(*idx)
+  }
+
   pub fn find_firs(&self, query: &str) -> Vec<FIR> {
     self
       .find_fir_indices(query)
@@ -327,6 +494,24 @@ impl FixedData {
     Some(self.airports[idx].clone())
   }
 
+  /// Airport and its field elevation in feet, the latter is the
+  /// average elevation of the runway thresholds as airports themselves
+  /// don't carry one
+  pub fn airport_elevation(&self, code: &str) -> Option<(&Airport, i32)> {
+    let idx = self.find_airport_idx(code)?;
+    let arpt = &self.airports[idx];
+    if arpt.runways.is_empty() {
+      return None;
+    }
+    let total: i64 = arpt
+      .runways
+      .values()
+      .map(|rwy| rwy.elevation_ft as i64)
+      .sum();
+    let elevation = total / arpt.runways.len() as i64;
+    Some((arpt, elevation as i32))
+  }
+
   pub fn find_airport_compound(&self, code: &str) -> Option<Airport> {
     let idx = self.arpt_compound_idx.get(code)?;
     let arpt = self.airports.get(*idx)?;
@@ -337,7 +522,399 @@ impl FixedData {
     self.geonames.get_country_by_position(position)
   }
 
+  /// FIRs containing the position, the most specific (smallest) ones first
+  pub fn find_firs_by_position(&self, position: Point) -> Vec<&FIR> {
+    let mut shapes: Vec<&FIRShape> = FIRShape::lookup_points(position)
+      .into_iter()
+      .flat_map(|p| {
+        self
+          .fir_shapes
+          .locate_in_envelope_intersecting(&AABB::from_point(p))
+      })
+      .filter(|shape| shape.contains(position))
+      .collect();
+    shapes.sort_by(|a, b| a.area.total_cmp(&b.area));
+    let mut seen = HashSet::new();
+    shapes
+      .into_iter()
+      .filter(|shape| seen.insert(shape.fir_idx))
+      .filter_map(|shape| self.firs.get(shape.fir_idx))
+      .collect()
+  }
+
+  /// FIR the position is in and the station controlling it, that's a
+  /// controller of the most specific controlled FIR containing the position
+  /// or of a UIR the FIR belongs to
+  pub fn locate(&self, position: Point) -> (Option<String>, Option<String>) {
+    let firs = self.find_firs_by_position(position);
+    let fir = firs.first().map(|fir| fir.icao.clone());
+    let controlled_by = firs
+      .iter()
+      .find_map(|fir| {
+        fir.controllers.keys().min().or_else(|| {
+          self
+            .fir_uirs_idx
+            .get(&fir.icao)?
+            .iter()
+            .find_map(|idx| self.uirs[*idx].controllers.keys().min())
+        })
+      })
+      .cloned();
+    (fir, controlled_by)
+  }
+
   pub fn get_geonames_country_by_id(&self, id: &str) -> Option<GeonamesCountry> {
     self.geonames.get_country_by_id(id)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    fixed::types::Boundaries,
+    moving::controller::{ControllerSet, Facility},
+    persistent::tests,
+  };
+
+  fn fir(icao: &str, points: &[(f64, f64)]) -> FIR {
+    let points: Vec<Point> = points
+      .iter()
+      .map(|(lng, lat)| Point {
+        lat: *lat,
+        lng: *lng,
+      })
+      .collect();
+    FIR {
+      icao: icao.into(),
+      name: icao.into(),
+      prefix: icao.into(),
+      boundaries: Boundaries {
+        id: icao.into(),
+        region: "".into(),
+        division: "".into(),
+        is_oceanic: false,
+        min: points[0],
+        max: points[2],
+        center: points[0],
+        points: vec![points],
+      },
+      controllers: HashMap::new(),
+      country: None,
+    }
+  }
+
+  #[test]
+  fn test_locate() {
+    let firs = vec![
+      fir(
+        "EGTT",
+        &[
+          (-6.0, 49.0),
+          (2.0, 49.0),
+          (2.0, 56.0),
+          (-6.0, 56.0),
+          (-6.0, 49.0),
+        ],
+      ),
+      fir(
+        "EGTX",
+        &[
+          (-1.0, 51.0),
+          (0.0, 51.0),
+          (0.0, 52.0),
+          (-1.0, 52.0),
+          (-1.0, 51.0),
+        ],
+      ),
+      // crosses the antimeridian
+      fir(
+        "NZZO",
+        &[
+          (170.0, -40.0),
+          (-170.0, -40.0),
+          (-170.0, -20.0),
+          (170.0, -20.0),
+          (170.0, -40.0),
+        ],
+      ),
+    ];
+    let mut fixed = FixedData::new(
+      vec![],
+      vec![],
+      firs,
+      vec![],
+      vec![],
+      Geonames::empty(),
+      ValidationReport::default(),
+    );
+
+    let heathrow = Point {
+      lat: 51.47,
+      lng: -0.45,
+    };
+    assert_eq!(fixed.locate(heathrow), (Some("EGTX".into()), None));
+    let ctrl = tests::test_controller("EGTT_CTR", Facility::Radar);
+    fixed.set_fir_controller(ctrl);
+    assert_eq!(
+      fixed.locate(heathrow),
+      (Some("EGTX".into()), Some("EGTT_CTR".into()))
+    );
+
+    let east = Point {
+      lat: -30.0,
+      lng: 175.0,
+    };
+    let west = Point {
+      lat: -30.0,
+      lng: -175.0,
+    };
+    assert_eq!(fixed.locate(east).0, Some("NZZO".into()));
+    assert_eq!(fixed.locate(west).0, Some("NZZO".into()));
+    let outside = Point {
+      lat: -30.0,
+      lng: 0.0,
+    };
+    assert_eq!(fixed.locate(outside), (None, None));
+  }
+
+  fn tracon(id: &str, prefixes: &[&str], suffix: Option<&str>) -> TRACON {
+    let p = Point { lat: 0.0, lng: 0.0 };
+    TRACON {
+      id: id.into(),
+      name: format!("{id} Approach"),
+      prefixes: prefixes.iter().map(|prefix| prefix.to_string()).collect(),
+      suffix: suffix.map(|suffix| suffix.into()),
+      min: p,
+      max: p,
+      center: p,
+      points: vec![],
+      controllers: HashMap::new(),
+    }
+  }
+
+  #[test]
+  fn test_tracon_controllers() {
+    let tracons = vec![
+      tracon("NCT", &["SFO", "OAK", "NCT"], None),
+      tracon("NCT", &["SFO"], Some("DEP")),
+      tracon("SCT", &["LAX", "SCT"], None),
+    ];
+    let mut fixed = FixedData::new(
+      vec![],
+      vec![],
+      vec![],
+      vec![],
+      tracons,
+      Geonames::empty(),
+      ValidationReport::default(),
+    );
+
+    let app = tests::test_controller("SFO_APP", Facility::Approach);
+    let tracon = fixed.set_tracon_controller(app.clone()).unwrap();
+    assert_eq!(tracon.key(), "NCT");
+    let dep = tests::test_controller("SFO_N_DEP", Facility::Approach);
+    let tracon = fixed.set_tracon_controller(dep.clone()).unwrap();
+    assert_eq!(tracon.key(), "NCT_DEP");
+    // OAK has no departure sector
+    let oak = tests::test_controller("OAK_DEP", Facility::Approach);
+    assert_eq!(fixed.set_tracon_controller(oak).unwrap().key(), "NCT");
+    let unknown = tests::test_controller("EGLL_APP", Facility::Approach);
+    assert!(fixed.set_tracon_controller(unknown).is_none());
+
+    let nct = fixed.find_tracon("NCT").unwrap();
+    assert_eq!(nct.controllers.len(), 2);
+    assert_eq!(
+      nct.controllers["SFO_APP"].human_readable.as_deref(),
+      Some("NCT Approach")
+    );
+    assert!(fixed.find_tracon("SCT").unwrap().is_empty());
+
+    fixed.reset_tracon_controller(&app);
+    fixed.reset_tracon_controller(&dep);
+    assert_eq!(fixed.find_tracon("NCT").unwrap().controllers.len(), 1);
+    assert!(fixed.find_tracon("NCT_DEP").unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_callsign_rules() {
+    let firs = vec![fir(
+      "EGTT",
+      &[
+        (-6.0, 49.0),
+        (2.0, 49.0),
+        (2.0, 56.0),
+        (-6.0, 56.0),
+        (-6.0, 49.0),
+      ],
+    )];
+    let tracons = vec![tracon("N90", &["N90"], None)];
+    let mut fixed = FixedData::new(
+      vec![],
+      vec![],
+      firs,
+      vec![],
+      tracons,
+      Geonames::empty(),
+      ValidationReport::default(),
+    );
+
+    let lon = tests::test_controller("LON_S_CTR", Facility::Radar);
+    assert!(fixed.set_fir_controller(lon.clone()).is_none());
+    let ny = tests::test_controller("NY_CAM_APP", Facility::Approach);
+    assert!(fixed.set_tracon_controller(ny.clone()).is_none());
+
+    let rules = CallsignRules::parse(
+      "[[rule]]\npattern = \"LON_.*CTR\"\nfir = \"EGTT\"\n\n[[rule]]\npattern = \"NY_.*APP\"\ntracon = \"N90\"\n",
+    )
+    .unwrap();
+    fixed.set_callsign_rules(rules);
+    assert_eq!(fixed.set_fir_controller(lon.clone()).unwrap().icao, "EGTT");
+    assert_eq!(
+      fixed.set_tracon_controller(ny.clone()).unwrap().key(),
+      "N90"
+    );
+
+    fixed.reset_fir_controller(&lon);
+    assert!(fixed.find_firs("EGTT")[0].is_empty());
+    fixed.reset_tracon_controller(&ny);
+    assert!(fixed.find_tracon("N90").unwrap().is_empty());
+
+    // a rule target too short for a country prefix
+    let rules = CallsignRules::parse("[[rule]]\npattern = \"LON_.*CTR\"\nfir = \"E\"\n").unwrap();
+    fixed.set_callsign_rules(rules);
+    assert!(fixed.set_fir_controller(lon).is_none());
+  }
+
+  #[test]
+  fn test_airport_controllers() {
+    let p = Point {
+      lat: 50.03,
+      lng: 8.57,
+    };
+    let airports = vec![Airport {
+      icao: "EDDF".into(),
+      iata: "FRA".into(),
+      name: "Frankfurt".into(),
+      position: p,
+      fir_id: "EDGG".into(),
+      is_pseudo: false,
+      controllers: ControllerSet::empty(),
+      runways: HashMap::new(),
+      country: None,
+      wx: None,
+    }];
+    let mut fixed = FixedData::new(
+      vec![],
+      airports,
+      vec![],
+      vec![],
+      vec![],
+      Geonames::empty(),
+      ValidationReport::default(),
+    );
+
+    let north = tests::test_controller("EDDF_N_TWR", Facility::Tower);
+    let south = tests::test_controller("EDDF_S_TWR", Facility::Tower);
+    let ground = tests::test_controller("EDDF_GND", Facility::Ground);
+    fixed.set_airport_controller(north.clone());
+    fixed.set_airport_controller(south.clone());
+    fixed.set_airport_controller(ground.clone());
+    // the same controller set again replaces itself
+    fixed.set_airport_controller(south.clone());
+
+    let arpt = fixed.find_airport("EDDF").unwrap();
+    assert_eq!(arpt.controllers.tower.len(), 2);
+    assert_eq!(
+      arpt.controllers.tower["EDDF_N_TWR"]
+        .human_readable
+        .as_deref(),
+      Some("Frankfurt Tower")
+    );
+    assert_eq!(arpt.controllers.ground.len(), 1);
+
+    fixed.reset_airport_controller(&north);
+    let arpt = fixed.find_airport("EDDF").unwrap();
+    assert_eq!(
+      arpt.controllers.tower.keys().collect::<Vec<_>>(),
+      vec!["EDDF_S_TWR"]
+    );
+
+    fixed.reset_airport_controller(&south);
+    fixed.reset_airport_controller(&ground);
+    assert!(fixed.find_airport("EDDF").unwrap().controllers.is_empty());
+  }
+
+  #[test]
+  fn test_uir_controllers() {
+    let firs = vec![
+      fir(
+        "EDWW",
+        &[
+          (6.0, 52.0),
+          (12.0, 52.0),
+          (12.0, 55.0),
+          (6.0, 55.0),
+          (6.0, 52.0),
+        ],
+      ),
+      fir(
+        "EDMM",
+        &[
+          (6.0, 47.0),
+          (12.0, 47.0),
+          (12.0, 52.0),
+          (6.0, 52.0),
+          (6.0, 47.0),
+        ],
+      ),
+    ];
+    let uirs = vec![UIR::new(
+      "EDUU",
+      "Rhein Radar",
+      vec!["EDWW".into(), "EDMM".into()],
+    )];
+    let mut fixed = FixedData::new(
+      vec![],
+      vec![],
+      firs,
+      uirs,
+      vec![],
+      Geonames::empty(),
+      ValidationReport::default(),
+    );
+
+    let uir = fixed.find_uir("EDUU").unwrap();
+    assert_eq!(uir.points.len(), 1);
+    assert_eq!((uir.min.lng, uir.min.lat), (6.0, 47.0));
+    assert_eq!((uir.max.lng, uir.max.lat), (12.0, 55.0));
+
+    let ctrl = tests::test_controller("EDUU_CTR", Facility::Radar);
+    assert!(fixed.set_fir_controller(ctrl.clone()).is_none());
+    let uir = fixed.set_uir_controller(ctrl.clone()).unwrap();
+    assert_eq!(
+      uir.controllers["EDUU_CTR"].human_readable.as_deref(),
+      Some("Rhein Radar")
+    );
+    // member FIRs are not staffed separately
+    assert!(fixed.firs().iter().all(|fir| fir.is_empty()));
+
+    let munich = Point {
+      lat: 48.35,
+      lng: 11.78,
+    };
+    assert_eq!(
+      fixed.locate(munich),
+      (Some("EDMM".into()), Some("EDUU_CTR".into()))
+    );
+
+    // FIR controllers take precedence over the UIR ones
+    let fir_ctrl = tests::test_controller("EDMM_CTR", Facility::Radar);
+    assert!(fixed.set_uir_controller(fir_ctrl.clone()).is_none());
+    fixed.set_fir_controller(fir_ctrl);
+    assert_eq!(fixed.locate(munich).1, Some("EDMM_CTR".into()));
+
+    fixed.reset_uir_controller(&ctrl);
+    assert!(fixed.find_uir("EDUU").unwrap().is_empty());
+  }
+}
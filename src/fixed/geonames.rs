@@ -38,9 +38,9 @@ impl Geonames {
     }
   }
 
-  pub async fn load(cfg: &Config) -> Result<Self, Box<dyn std::error::Error>> {
-    let countries = load_countries(cfg).await?;
-    let geonames_shapes = load_shapes(cfg).await?;
+  pub async fn load(cfg: &Config, refresh: bool) -> Result<Self, Box<dyn std::error::Error>> {
+    let countries = load_countries(cfg, refresh).await?;
+    let geonames_shapes = load_shapes(cfg, refresh).await?;
     let countries2d = RTree::bulk_load(geonames_shapes);
 
     Ok(Self {
@@ -114,10 +114,14 @@ fn parse_countries(
 
 async fn load_countries(
   cfg: &Config,
+  refresh: bool,
 ) -> Result<HashMap<String, GeonamesCountry>, Box<dyn std::error::Error>> {
   let cache_file = cached_loader(
+    &cfg.fixed,
     &cfg.fixed.geonames_countries_url,
     &cfg.cache.geonames_countries,
+    cfg.cache.geonames_countries_max_age,
+    refresh,
   )
   .await?;
 
@@ -127,9 +131,18 @@ async fn load_countries(
   Ok(countries)
 }
 
-async fn load_shapes(cfg: &Config) -> Result<Vec<GeonamesShape>, Box<dyn std::error::Error>> {
-  let cache_file =
-    cached_loader(&cfg.fixed.geonames_shapes_url, &cfg.cache.geonames_shapes).await?;
+async fn load_shapes(
+  cfg: &Config,
+  refresh: bool,
+) -> Result<Vec<GeonamesShape>, Box<dyn std::error::Error>> {
+  let cache_file = cached_loader(
+    &cfg.fixed,
+    &cfg.fixed.geonames_shapes_url,
+    &cfg.cache.geonames_shapes,
+    cfg.cache.geonames_shapes_max_age,
+    refresh,
+  )
+  .await?;
   let t = Utc::now();
   let mut z = ZipArchive::new(cache_file)?;
   let mut raw_data = String::new();
@@ -1,37 +1,300 @@
 /// Fixed data provider
 /// This includes vatspy-data-project's items like Countries, Airports,
-/// FIRs and UIRs as well as ourairports' data on runways
+/// FIRs and UIRs as well as ourairports' data on runways and optional
+/// SimAware TRACON boundaries
 mod boundaries;
+mod cache;
+pub mod callsigns;
 pub mod data;
 pub mod errors;
 pub mod geonames;
 pub mod ourairports;
 pub mod parser;
+mod tracons;
 pub mod types;
+pub mod validate;
 
-use std::{error::Error, fs::File, io::Write, path::Path};
+use std::{
+  error::Error,
+  fs::File,
+  path::{Path, PathBuf},
+  time::Duration,
+};
 
 use chrono::Utc;
-use log::info;
+use log::{info, warn};
+use reqwest::{
+  header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+  Client, StatusCode,
+};
 
-use crate::seconds_since;
+use self::cache::{write_atomic, CacheMeta};
+use crate::{config::Fixed as FixedConfig, seconds_since};
 
-async fn cached_loader(url: &str, cache_filename: &str) -> Result<File, Box<dyn Error>> {
-  let path = Path::new(&cache_filename);
-  if !path.is_file() {
-    info!("fetching {url} from web");
-    let t = Utc::now();
-    let data = reqwest::get(url).await?.bytes().await?;
-    let mut cache_file = File::create(path)?;
-    cache_file.write_all(&data)?;
-    info!(
-      "data loaded from web in {}s and stored in {cache_filename}",
-      seconds_since(t)
-    );
+// where a fixed data file comes from
+#[derive(Debug, PartialEq)]
+enum Source<'a> {
+  Local(PathBuf),
+  Remote(&'a str),
+}
+
+// file:// urls are read as is, all the other files are looked up in
+// data_dir by the url file name if data_dir is set
+fn source<'a>(url: &'a str, data_dir: Option<&str>) -> Source<'a> {
+  if let Some(path) = url.strip_prefix("file://") {
+    Source::Local(path.into())
+  } else if let Some(data_dir) = data_dir {
+    let name = url.rsplit('/').next().unwrap_or(url);
+    Source::Local(Path::new(data_dir).join(name))
   } else {
-    info!("{cache_filename} found, skipping fetching")
+    Source::Remote(url)
+  }
+}
+
+enum Fetched {
+  NotModified,
+  Data {
+    data: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+  },
+}
+
+// conditional request if the cached file has validators
+async fn fetch(url: &str, meta: Option<&CacheMeta>) -> Result<Fetched, Box<dyn Error>> {
+  let mut req = Client::new().get(url);
+  if let Some(meta) = meta {
+    if let Some(etag) = &meta.etag {
+      req = req.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &meta.last_modified {
+      req = req.header(IF_MODIFIED_SINCE, last_modified);
+    }
+  }
+
+  let resp = req.send().await?;
+  if resp.status() == StatusCode::NOT_MODIFIED && meta.is_some() {
+    return Ok(Fetched::NotModified);
+  }
+  let resp = resp.error_for_status()?;
+  let header = |name| {
+    resp
+      .headers()
+      .get(name)
+      .and_then(|v| v.to_str().ok())
+      .map(|v| v.to_owned())
+  };
+  let etag = header(ETAG);
+  let last_modified = header(LAST_MODIFIED);
+  let expected_len = resp.content_length();
+
+  let data = resp.bytes().await?.to_vec();
+  if let Some(expected_len) = expected_len {
+    if data.len() as u64 != expected_len {
+      let msg = format!(
+        "truncated download: got {} bytes of {expected_len}",
+        data.len()
+      );
+      return Err(msg.into());
+    }
+  }
+  Ok(Fetched::Data {
+    data,
+    etag,
+    last_modified,
+  })
+}
+
+/// Opens a fixed data file. Remote files are kept in cache_filename and
+/// revalidated with the server once they're older than max_age, refresh
+/// makes them revalidated right away. If the server can't be reached a
+/// valid outdated copy is used. Local files are always read in place.
+async fn cached_loader(
+  cfg: &FixedConfig,
+  url: &str,
+  cache_filename: &str,
+  max_age: Duration,
+  refresh: bool,
+) -> Result<File, Box<dyn Error>> {
+  let url = match source(url, cfg.data_dir.as_deref()) {
+    Source::Local(path) => {
+      info!("reading {} from local file", path.display());
+      let f = File::open(path)?;
+      return Ok(f);
+    }
+    Source::Remote(url) => url,
+  };
+
+  let path = Path::new(&cache_filename);
+  let meta = CacheMeta::load(path, url);
+  match &meta {
+    Some(meta) if !refresh && meta.is_fresh(max_age, Utc::now()) => {
+      info!("{cache_filename} is up to date, skipping fetching");
+    }
+    _ => {
+      info!("fetching {url} from web");
+      let t = Utc::now();
+      let res = fetch(url, meta.as_ref()).await;
+      match res {
+        Ok(Fetched::NotModified) => {
+          let mut meta = meta.unwrap();
+          meta.fetched_at = Utc::now();
+          meta.save(path)?;
+          info!("{url} not modified, using {cache_filename}");
+        }
+        Ok(Fetched::Data {
+          data,
+          etag,
+          last_modified,
+        }) => {
+          write_atomic(path, &data)?;
+          CacheMeta::new(url, &data, etag, last_modified).save(path)?;
+          info!(
+            "data loaded from web in {}s and stored in {cache_filename}",
+            seconds_since(t)
+          );
+        }
+        Err(err) => {
+          if meta.is_none() {
+            return Err(err);
+          }
+          warn!("error fetching {url}: {err}, using outdated {cache_filename}");
+        }
+      }
+    }
   }
 
   let f = File::open(path)?;
   Ok(f)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{env::temp_dir, fs, io::Read};
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+  use uuid::Uuid;
+
+  // serves "runways" with an etag, answers 304 to conditional requests
+  async fn serve_runways() -> (String, tokio::task::JoinHandle<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/runways.csv", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+      let mut full_responses = 0;
+      loop {
+        let (mut conn, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0; 4096];
+        let n = conn.read(&mut buf).await.unwrap();
+        let req = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+        let resp = if req.contains("if-none-match: \"v1\"") {
+          "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\nconnection: close\r\n\r\n".to_owned()
+        } else {
+          full_responses += 1;
+          "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: 7\r\nconnection: close\r\n\r\nrunways"
+            .to_owned()
+        };
+        conn.write_all(resp.as_bytes()).await.unwrap();
+        conn.shutdown().await.unwrap();
+        if req.contains("/stop") {
+          return full_responses;
+        }
+      }
+    });
+    (url, handle)
+  }
+
+  fn read_all(mut f: File) -> String {
+    let mut data = String::new();
+    f.read_to_string(&mut data).unwrap();
+    data
+  }
+
+  #[test]
+  fn test_source() {
+    let url = "https://ourairports.com/data/runways.csv";
+    assert_eq!(source(url, None), Source::Remote(url));
+    assert_eq!(
+      source(url, Some("/opt/camden")),
+      Source::Local("/opt/camden/runways.csv".into())
+    );
+    assert_eq!(
+      source("file:///opt/VATSpy.dat", Some("/opt/camden")),
+      Source::Local("/opt/VATSpy.dat".into())
+    );
+  }
+
+  #[tokio::test]
+  async fn test_remote_loader() {
+    let dir = temp_dir().join(format!("camden-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let cache = dir.join("runways.cache");
+    let cache = cache.to_str().unwrap();
+    let cfg = FixedConfig::default();
+    let (url, server) = serve_runways().await;
+    let day = Duration::from_secs(86400);
+
+    let f = cached_loader(&cfg, &url, cache, day, false).await.unwrap();
+    assert_eq!(read_all(f), "runways");
+    let meta = CacheMeta::load(Path::new(cache), &url).unwrap();
+    assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+
+    // fresh cache is used as is
+    let f = cached_loader(&cfg, &url, cache, day, false).await.unwrap();
+    assert_eq!(read_all(f), "runways");
+    // outdated cache is revalidated
+    let f = cached_loader(&cfg, &url, cache, Duration::ZERO, false)
+      .await
+      .unwrap();
+    assert_eq!(read_all(f), "runways");
+    let revalidated = CacheMeta::load(Path::new(cache), &url).unwrap();
+    assert!(revalidated.fetched_at > meta.fetched_at);
+
+    // corrupted cache is fetched again
+    fs::write(cache, "corrupted").unwrap();
+    let f = cached_loader(&cfg, &url, cache, day, false).await.unwrap();
+    assert_eq!(read_all(f), "runways");
+
+    let stop_url = url.replace("runways.csv", "stop");
+    reqwest::get(stop_url).await.unwrap();
+    assert_eq!(server.await.unwrap(), 3);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_local_loader() {
+    let dir = temp_dir().join(format!("camden-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("runways.csv"), "runways").unwrap();
+    fs::write(dir.join("VATSpy.dat"), "vatspy").unwrap();
+
+    let cfg = FixedConfig {
+      data_dir: Some(dir.to_str().unwrap().to_owned()),
+      ..Default::default()
+    };
+    let cache = dir.join("runways.cache");
+    let cache = cache.to_str().unwrap();
+    let mut f = cached_loader(&cfg, &cfg.runways_url, cache, Duration::ZERO, true)
+      .await
+      .unwrap();
+    let mut data = String::new();
+    f.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "runways");
+    // local files are not cached
+    assert!(!Path::new(cache).exists());
+
+    let url = format!("file://{}", dir.join("VATSpy.dat").display());
+    let mut f = cached_loader(&cfg, &url, cache, Duration::ZERO, false)
+      .await
+      .unwrap();
+    let mut data = String::new();
+    f.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "vatspy");
+
+    let res = cached_loader(&cfg, &cfg.boundaries_url, cache, Duration::ZERO, false).await;
+    assert!(res.is_err());
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
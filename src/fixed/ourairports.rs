@@ -139,8 +139,18 @@ async fn parse(src: File) -> Result<HashMap<String, Vec<Runway>>, Box<dyn Error>
   Ok(runways)
 }
 
-pub async fn load_runways(cfg: &Config) -> Result<HashMap<String, Vec<Runway>>, Box<dyn Error>> {
-  let cache_file = cached_loader(&cfg.fixed.runways_url, &cfg.cache.runways).await?;
+pub async fn load_runways(
+  cfg: &Config,
+  refresh: bool,
+) -> Result<HashMap<String, Vec<Runway>>, Box<dyn Error>> {
+  let cache_file = cached_loader(
+    &cfg.fixed,
+    &cfg.fixed.runways_url,
+    &cfg.cache.runways,
+    cfg.cache.runways_max_age,
+    refresh,
+  )
+  .await?;
   let t = Utc::now();
   let res = parse(cache_file).await;
   info!("runways data parsed in {}s", seconds_since(t));
@@ -1,13 +1,17 @@
 use super::{
   boundaries::load_boundaries,
+  cached_loader,
+  callsigns::CallsignRules,
   data::FixedData,
   geonames::Geonames,
   ourairports::{load_runways, Runway},
-  types::{Airport, Boundaries, Country, FIR, UIR},
+  tracons::load_tracons,
+  types::{Airport, Boundaries, Country, FIR, TRACON, UIR},
+  validate::ValidationReport,
 };
 use crate::{config::Config, moving::controller::ControllerSet, types::Point};
-use log::error;
-use std::{collections::HashMap, error::Error, fmt::Display};
+use log::{error, info};
+use std::{collections::HashMap, error::Error, fmt::Display, io::Read};
 
 enum ParserState {
   Idle,
@@ -33,6 +37,7 @@ fn parse(
   src: &str,
   bdrs: HashMap<String, Boundaries>,
   mut runway_map: HashMap<String, Vec<Runway>>,
+  tracons: Vec<TRACON>,
   geonames: Geonames,
 ) -> Result<FixedData, ParseError> {
   let mut state = ParserState::Idle;
@@ -40,6 +45,7 @@ fn parse(
   let mut airports = vec![];
   let mut firs = vec![];
   let mut uirs = vec![];
+  let mut report = ValidationReport::default();
 
   for line in src.lines() {
     let line = line.trim();
@@ -59,11 +65,15 @@ fn parse(
       }
     } else {
       match state {
-        ParserState::Idle => error!("unexpected line \"{}\" while parser is idle", line),
+        ParserState::Idle => {
+          error!("unexpected line \"{}\" while parser is idle", line);
+          report.invalid_lines.push(line.to_owned());
+        }
         ParserState::ReadCountries => {
           let tokens: Vec<&str> = line.split('|').collect();
           if tokens.len() != 3 {
-            error!("invalid country line \"{}\"", line)
+            error!("invalid country line \"{}\"", line);
+            report.invalid_lines.push(line.to_owned());
           } else {
             let c = Country {
               name: tokens[0].into(),
@@ -80,7 +90,8 @@ fn parse(
         ParserState::ReadAirports => {
           let tokens: Vec<&str> = line.split('|').collect();
           if tokens.len() != 7 {
-            error!("invalid airport line \"{}\"", line)
+            error!("invalid airport line \"{}\"", line);
+            report.invalid_lines.push(line.to_owned());
           } else {
             let lat = tokens[2].parse::<f64>();
             if lat.is_err() {
@@ -88,6 +99,7 @@ fn parse(
                 "can't parse latitude \"{}\" for airport {}",
                 tokens[2], tokens[0]
               );
+              report.invalid_lines.push(line.to_owned());
               continue;
             }
             let lng = tokens[3].parse::<f64>();
@@ -96,6 +108,7 @@ fn parse(
                 "can't parse longitude \"{}\" for airport {}",
                 tokens[3], tokens[0]
               );
+              report.invalid_lines.push(line.to_owned());
               continue;
             }
 
@@ -133,7 +146,8 @@ fn parse(
         ParserState::ReadFIRs => {
           let tokens: Vec<&str> = line.split('|').collect();
           if tokens.len() != 4 {
-            error!("invalid fir line \"{}\"", line)
+            error!("invalid fir line \"{}\"", line);
+            report.invalid_lines.push(line.to_owned());
           } else {
             let mut b_id = tokens[3];
 
@@ -161,20 +175,18 @@ fn parse(
                 "can't find boundaries \"{}\" for FIR \"{}\"",
                 tokens[3], tokens[0]
               );
+              report.firs_without_boundaries.push(tokens[0].to_owned());
             }
           }
         }
         ParserState::ReadUIRs => {
           let tokens: Vec<&str> = line.split('|').collect();
           if tokens.len() != 3 {
-            error!("invalid uir line \"{}\"", line)
+            error!("invalid uir line \"{}\"", line);
+            report.invalid_lines.push(line.to_owned());
           } else {
             let fir_ids = tokens[2].split(',').map(|t| t.into()).collect();
-            let uir = UIR {
-              icao: tokens[0].into(),
-              name: tokens[1].into(),
-              fir_ids,
-            };
+            let uir = UIR::new(tokens[0], tokens[1], fir_ids);
             uirs.push(uir);
           }
         }
@@ -182,14 +194,33 @@ fn parse(
     }
   }
 
-  Ok(FixedData::new(countries, airports, firs, uirs, geonames))
+  report.check(&airports, &firs, &uirs);
+  Ok(FixedData::new(
+    countries, airports, firs, uirs, tracons, geonames, report,
+  ))
 }
 
-pub async fn load_fixed(cfg: &Config) -> Result<FixedData, Box<dyn Error>> {
-  let boundaries = load_boundaries(&cfg.fixed.boundaries_url).await?;
-  let text = reqwest::get(&cfg.fixed.data_url).await?.text().await?;
-  let runways = load_runways(cfg).await?;
-  let geonames = Geonames::load(cfg).await?;
-  let data = parse(&text, boundaries, runways, geonames)?;
+// refresh makes cached remote files revalidated regardless of their age
+pub async fn load_fixed(cfg: &Config, refresh: bool) -> Result<FixedData, Box<dyn Error>> {
+  let boundaries = load_boundaries(cfg, refresh).await?;
+  let mut cache_file = cached_loader(
+    &cfg.fixed,
+    &cfg.fixed.data_url,
+    &cfg.cache.data,
+    cfg.cache.data_max_age,
+    refresh,
+  )
+  .await?;
+  let mut text = String::new();
+  cache_file.read_to_string(&mut text)?;
+  let runways = load_runways(cfg, refresh).await?;
+  let tracons = load_tracons(cfg, refresh).await?;
+  let geonames = Geonames::load(cfg, refresh).await?;
+  let mut data = parse(&text, boundaries, runways, tracons, geonames)?;
+  if let Some(path) = &cfg.fixed.callsign_rules {
+    let rules = CallsignRules::load(path)?;
+    info!("{} callsign rules loaded from {}", rules.len(), path);
+    data.set_callsign_rules(rules);
+  }
   Ok(data)
 }
@@ -0,0 +1,179 @@
+use super::{
+  boundaries::{extract_geometry, Geometry},
+  cached_loader,
+  types::TRACON,
+};
+use crate::config::Config;
+use geojson::{Feature, FeatureCollection, GeoJson};
+use log::{error, info};
+use serde_json::Value;
+use std::{collections::HashMap, error::Error, io::Read};
+
+// prefix is a list of callsign prefixes in SimAware data,
+// a single string is accepted as well
+fn extract_prefixes(value: &Value) -> Option<Vec<String>> {
+  match value {
+    Value::String(prefix) => Some(vec![prefix.clone()]),
+    Value::Array(prefixes) => Some(
+      prefixes
+        .iter()
+        .filter_map(|prefix| prefix.as_str())
+        .map(|prefix| prefix.to_owned())
+        .collect(),
+    ),
+    _ => None,
+  }
+}
+
+fn extract_tracon(feat: &Feature) -> Option<TRACON> {
+  let props = &feat.properties;
+  let geom = feat.geometry.as_ref()?;
+  if let Some(props) = props {
+    let id = props.get("id")?.as_str()?.to_owned();
+    let name = props.get("name")?.as_str()?.to_owned();
+    let prefixes = extract_prefixes(props.get("prefix")?)?;
+    let suffix = props
+      .get("suffix")
+      .and_then(|suffix| suffix.as_str())
+      .filter(|suffix| !suffix.is_empty())
+      .map(|suffix| suffix.to_owned());
+    let geometry = extract_geometry(&geom.value)?;
+
+    Some(TRACON {
+      id,
+      name,
+      prefixes,
+      suffix,
+      min: geometry.min,
+      max: geometry.max,
+      center: geometry.center,
+      points: geometry.points,
+      controllers: HashMap::new(),
+    })
+  } else {
+    error!("no props found in feature {:?}", feat);
+    None
+  }
+}
+
+// a sector may be split into several features, they're merged
+// into a single TRACON
+fn merge(tracon: &mut TRACON, other: TRACON) {
+  let mut points = std::mem::take(&mut tracon.points);
+  points.extend(other.points);
+  let geometry = Geometry::from_points(points);
+  tracon.min = geometry.min;
+  tracon.max = geometry.max;
+  tracon.center = geometry.center;
+  tracon.points = geometry.points;
+  for prefix in other.prefixes {
+    if !tracon.prefixes.contains(&prefix) {
+      tracon.prefixes.push(prefix);
+    }
+  }
+}
+
+fn parse(raw_geojson: &str) -> Result<Vec<TRACON>, Box<dyn Error>> {
+  let geo = raw_geojson.parse::<GeoJson>()?;
+  let coll = FeatureCollection::try_from(geo)?;
+  let mut res: Vec<TRACON> = vec![];
+  let mut idx = HashMap::new();
+  for feature in coll {
+    let tracon = extract_tracon(&feature);
+    if let Some(tracon) = tracon {
+      let key = tracon.key();
+      if let Some(existing) = idx.get(&key) {
+        merge(&mut res[*existing], tracon);
+      } else {
+        idx.insert(key, res.len());
+        res.push(tracon);
+      }
+    } else {
+      error!("invalid tracon feature {:?}", feature.properties);
+    }
+  }
+  Ok(res)
+}
+
+pub async fn load_tracons(cfg: &Config, refresh: bool) -> Result<Vec<TRACON>, Box<dyn Error>> {
+  let url = match &cfg.fixed.tracon_boundaries_url {
+    Some(url) => url,
+    None => return Ok(vec![]),
+  };
+  let mut cache_file = cached_loader(
+    &cfg.fixed,
+    url,
+    &cfg.cache.tracon_boundaries,
+    cfg.cache.tracon_boundaries_max_age,
+    refresh,
+  )
+  .await?;
+  let mut raw_geojson = String::new();
+  cache_file.read_to_string(&mut raw_geojson)?;
+  let tracons = parse(&raw_geojson)?;
+  info!("{} tracon boundaries loaded", tracons.len());
+  Ok(tracons)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::parse;
+
+  const TEST_TRACONS: &str = r#"{
+    "type": "FeatureCollection",
+    "features": [
+      {
+        "type": "Feature",
+        "properties": { "id": "NCT", "prefix": ["SFO", "OAK", "NCT"], "name": "NorCal Approach" },
+        "geometry": {
+          "type": "MultiPolygon",
+          "coordinates": [[[[-123.0, 37.0], [-121.5, 37.0], [-121.5, 38.5], [-123.0, 38.5], [-123.0, 37.0]]]]
+        }
+      },
+      {
+        "type": "Feature",
+        "properties": { "id": "NCT", "prefix": "SFO", "suffix": "DEP", "name": "NorCal Departure" },
+        "geometry": {
+          "type": "Polygon",
+          "coordinates": [[[-122.8, 37.2], [-122.0, 37.2], [-122.0, 38.0], [-122.8, 38.0], [-122.8, 37.2]]]
+        }
+      },
+      {
+        "type": "Feature",
+        "properties": { "id": "NCT", "prefix": ["SJC"], "name": "NorCal Approach" },
+        "geometry": {
+          "type": "Polygon",
+          "coordinates": [[[-122.5, 36.5], [-121.0, 36.5], [-121.0, 37.5], [-122.5, 37.5], [-122.5, 36.5]]]
+        }
+      },
+      {
+        "type": "Feature",
+        "properties": { "id": "XXX", "name": "No prefix" },
+        "geometry": { "type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]] }
+      }
+    ]
+  }"#;
+
+  #[test]
+  fn test_parse() {
+    let tracons = parse(TEST_TRACONS).unwrap();
+    assert_eq!(tracons.len(), 2);
+
+    let nct = &tracons[0];
+    assert_eq!(nct.key(), "NCT");
+    assert_eq!(nct.name, "NorCal Approach");
+    assert_eq!(nct.prefixes, vec!["SFO", "OAK", "NCT", "SJC"]);
+    assert_eq!(nct.suffix, None);
+    // the second NCT feature is merged
+    assert_eq!(nct.points.len(), 2);
+    assert_eq!(nct.min.lng, -123.0);
+    assert_eq!(nct.min.lat, 36.5);
+    assert_eq!(nct.max.lng, -121.0);
+    assert_eq!(nct.max.lat, 38.5);
+
+    let dep = &tracons[1];
+    assert_eq!(dep.key(), "NCT_DEP");
+    assert_eq!(dep.prefixes, vec!["SFO"]);
+    assert_eq!(dep.suffix.as_deref(), Some("DEP"));
+  }
+}
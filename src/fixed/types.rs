@@ -5,6 +5,7 @@ use crate::{
   types::Point,
   weather::WeatherInfo,
 };
+use geo::{Area, Contains};
 use geo_types::Polygon;
 use geo_types::{geometry::Coord, LineString};
 use geojson::{Feature, Value};
@@ -46,9 +47,11 @@ impl Airport {
     }
   }
 
+  // all the ATIS stations are taken into account as arrivals and
+  // departures may be announced separately, i.e. EDDF_A_ATIS and EDDF_D_ATIS
   pub fn set_active_runways(&mut self) {
     self.reset_active_runways();
-    if let Some(atis) = &self.controllers.atis {
+    for atis in self.controllers.atis.values() {
       let norm_atis = normalize_atis_text(&atis.text_atis, true);
       let arrivals = detect_arrivals(&norm_atis);
       let departures = detect_departures(&norm_atis);
@@ -85,14 +88,77 @@ impl FIR {
   }
 }
 
-#[derive(Debug, Clone)]
+/// Approach sector from SimAware TRACON boundaries, APP controllers are
+/// matched by their callsign prefix and, if set, suffix (i.e. SFO_DEP)
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TRACON {
+  pub id: String,
+  pub name: String,
+  pub prefixes: Vec<String>,
+  pub suffix: Option<String>,
+  pub min: Point,
+  pub max: Point,
+  pub center: Point,
+  pub points: Vec<Vec<Point>>,
+  pub controllers: HashMap<String, Controller>,
+}
+
+impl TRACON {
+  /// Unique TRACON id, sectors sharing an id differ in suffix
+  pub fn key(&self) -> String {
+    match &self.suffix {
+      Some(suffix) => format!("{}_{}", self.id, suffix),
+      None => self.id.clone(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.controllers.is_empty()
+  }
+}
+
+/// UIR covering several FIRs, its boundaries are merged from the member
+/// FIRs ones. Controllers of a UIR are kept on the UIR itself rather than
+/// on each of the FIRs.
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct UIR {
   pub icao: String,
   pub name: String,
   pub fir_ids: Vec<String>,
+  pub min: Point,
+  pub max: Point,
+  pub center: Point,
+  pub points: Vec<Vec<Point>>,
+  pub controllers: HashMap<String, Controller>,
+  // country of the first member FIR
+  #[serde(skip_serializing)]
+  pub country: Option<GeonamesCountry>,
+}
+
+impl UIR {
+  pub fn new(icao: &str, name: &str, fir_ids: Vec<String>) -> Self {
+    let p = Point { lat: 0.0, lng: 0.0 };
+    Self {
+      icao: icao.into(),
+      name: name.into(),
+      fir_ids,
+      min: p,
+      max: p,
+      center: p,
+      points: vec![],
+      controllers: HashMap::new(),
+      country: None,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.controllers.is_empty()
+  }
 }
 
-#[derive(Debug, Clone, Serialize)]
+// compared as a whole, geometry included, as fixed data reloads may
+// replace boundaries at runtime and clients have to get the new ones
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct Boundaries {
   pub id: String,
   pub region: String,
@@ -104,13 +170,6 @@ pub struct Boundaries {
   pub points: Vec<Vec<Point>>,
 }
 
-impl PartialEq for Boundaries {
-  // simplify partial eq as boundaries don't change within a single app run
-  fn eq(&self, other: &Self) -> bool {
-    self.id == other.id
-  }
-}
-
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct GeonamesCountry {
   pub iso: String,
@@ -172,6 +231,69 @@ impl GeonamesShape {
   }
 }
 
+/// A single FIR boundaries polygon for point-in-polygon lookups. Polygons
+/// crossing the antimeridian are unwrapped to 0..360 longitudes so they
+/// stay continuous.
+#[derive(Debug, Clone)]
+pub struct FIRShape {
+  pub poly: Polygon,
+  // index of the FIR in FixedData
+  pub fir_idx: usize,
+  pub area: f64,
+}
+
+impl RTreeObject for FIRShape {
+  type Envelope = AABB<geo_types::Point<f64>>;
+
+  fn envelope(&self) -> Self::Envelope {
+    self.poly.envelope()
+  }
+}
+
+impl FIRShape {
+  pub fn new(fir_idx: usize, points: &[Point]) -> Option<Self> {
+    if points.len() < 3 {
+      return None;
+    }
+    let crosses_antimeridian = points
+      .windows(2)
+      .any(|pair| (pair[0].lng - pair[1].lng).abs() > 180.0);
+    let coords: Vec<Coord> = points
+      .iter()
+      .map(|p| Coord {
+        x: if crosses_antimeridian && p.lng < 0.0 {
+          p.lng + 360.0
+        } else {
+          p.lng
+        },
+        y: p.lat,
+      })
+      .collect();
+    let poly = Polygon::new(LineString::from(coords), vec![]);
+    let area = poly.unsigned_area();
+    Some(Self {
+      poly,
+      fir_idx,
+      area,
+    })
+  }
+
+  // positions west of Greenwich are checked against unwrapped polygons too
+  pub fn lookup_points(position: Point) -> Vec<geo_types::Point<f64>> {
+    let mut points = vec![position.into()];
+    if position.lng < 0.0 {
+      points.push(geo_types::Point::new(position.lng + 360.0, position.lat));
+    }
+    points
+  }
+
+  pub fn contains(&self, position: Point) -> bool {
+    Self::lookup_points(position)
+      .iter()
+      .any(|p| self.poly.contains(p))
+  }
+}
+
 #[derive(Debug)]
 pub enum GeonamesShapeSet {
   Single(GeonamesShape),
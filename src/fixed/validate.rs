@@ -0,0 +1,260 @@
+/// Fixed data validation
+/// VATSpy data is checked for consistency while it's parsed, the report
+/// is kept along with the data so regressions don't go unnoticed.
+/// Problems making parts of the data unusable are errors, the rest
+/// are warnings.
+use super::types::{Airport, FIR, UIR};
+use serde::Serialize;
+use std::{
+  collections::{BTreeMap, HashSet},
+  fmt::Display,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownFIRs {
+  pub uir: String,
+  pub firs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AirportFIR {
+  pub airport: String,
+  pub fir: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateIATA {
+  pub iata: String,
+  // compound ids, the last one wins in the IATA index
+  pub airports: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+  // errors
+  pub invalid_lines: Vec<String>,
+  pub firs_without_boundaries: Vec<String>,
+  pub uirs_with_unknown_firs: Vec<UnknownFIRs>,
+  // warnings
+  pub airports_without_fir: Vec<AirportFIR>,
+  pub duplicate_iata: Vec<DuplicateIATA>,
+  pub airports_without_runways: Vec<String>,
+}
+
+impl ValidationReport {
+  /// Checks parsed objects, parser problems are expected
+  /// to be collected in the report already
+  pub fn check(&mut self, airports: &[Airport], firs: &[FIR], uirs: &[UIR]) {
+    let fir_ids: HashSet<&str> = firs.iter().map(|fir| fir.icao.as_str()).collect();
+
+    for uir in uirs {
+      let unknown: Vec<String> = uir
+        .fir_ids
+        .iter()
+        .filter(|id| !fir_ids.contains(id.as_str()))
+        .cloned()
+        .collect();
+      if !unknown.is_empty() {
+        self.uirs_with_unknown_firs.push(UnknownFIRs {
+          uir: uir.icao.clone(),
+          firs: unknown,
+        });
+      }
+    }
+
+    let mut by_iata: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for arpt in airports {
+      if !fir_ids.contains(arpt.fir_id.as_str()) {
+        self.airports_without_fir.push(AirportFIR {
+          airport: arpt.icao.clone(),
+          fir: arpt.fir_id.clone(),
+        });
+      }
+      if !arpt.iata.is_empty() {
+        by_iata
+          .entry(arpt.iata.as_str())
+          .or_default()
+          .push(arpt.compound_id());
+      }
+      // pseudo airports are not real airfields
+      if !arpt.is_pseudo && arpt.runways.is_empty() {
+        self.airports_without_runways.push(arpt.icao.clone());
+      }
+    }
+
+    for (iata, airports) in by_iata {
+      if airports.len() > 1 {
+        self.duplicate_iata.push(DuplicateIATA {
+          iata: iata.to_owned(),
+          airports,
+        });
+      }
+    }
+  }
+
+  pub fn errors(&self) -> usize {
+    self.invalid_lines.len()
+      + self.firs_without_boundaries.len()
+      + self.uirs_with_unknown_firs.len()
+  }
+
+  pub fn warnings(&self) -> usize {
+    self.airports_without_fir.len()
+      + self.duplicate_iata.len()
+      + self.airports_without_runways.len()
+  }
+
+  pub fn has_errors(&self) -> bool {
+    self.errors() > 0
+  }
+}
+
+impl Display for ValidationReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for line in &self.invalid_lines {
+      writeln!(f, "error: invalid line \"{line}\"")?;
+    }
+    for fir in &self.firs_without_boundaries {
+      writeln!(f, "error: no boundaries found for FIR {fir}")?;
+    }
+    for item in &self.uirs_with_unknown_firs {
+      writeln!(
+        f,
+        "error: UIR {} refers to unknown FIRs {}",
+        item.uir,
+        item.firs.join(",")
+      )?;
+    }
+    for item in &self.airports_without_fir {
+      writeln!(
+        f,
+        "warning: airport {} refers to unknown FIR \"{}\"",
+        item.airport, item.fir
+      )?;
+    }
+    for item in &self.duplicate_iata {
+      writeln!(
+        f,
+        "warning: IATA code {} is shared by {}",
+        item.iata,
+        item.airports.join(",")
+      )?;
+    }
+    for arpt in &self.airports_without_runways {
+      writeln!(f, "warning: no runways found for airport {arpt}")?;
+    }
+    write!(f, "{} errors, {} warnings", self.errors(), self.warnings())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    fixed::{ourairports::Runway, types::Boundaries},
+    moving::controller::ControllerSet,
+    types::Point,
+  };
+  use std::collections::HashMap;
+
+  fn airport(icao: &str, iata: &str, fir_id: &str, with_runways: bool) -> Airport {
+    let mut runways = HashMap::new();
+    if with_runways {
+      runways.insert(
+        "09".to_owned(),
+        Runway {
+          icao: icao.into(),
+          length_ft: 10000,
+          width_ft: 150,
+          surface: "ASP".into(),
+          lighted: true,
+          closed: false,
+          ident: "09".into(),
+          latitude: 0.0,
+          longitude: 0.0,
+          elevation_ft: 0,
+          heading: 90,
+          active_to: false,
+          active_lnd: false,
+        },
+      );
+    }
+    Airport {
+      icao: icao.into(),
+      iata: iata.into(),
+      name: icao.into(),
+      position: Point { lat: 0.0, lng: 0.0 },
+      fir_id: fir_id.into(),
+      is_pseudo: false,
+      controllers: ControllerSet::empty(),
+      runways,
+      country: None,
+      wx: None,
+    }
+  }
+
+  fn fir(icao: &str) -> FIR {
+    let p = Point { lat: 0.0, lng: 0.0 };
+    FIR {
+      icao: icao.into(),
+      name: icao.into(),
+      prefix: icao.into(),
+      boundaries: Boundaries {
+        id: icao.into(),
+        region: "EMEA".into(),
+        division: "EUD".into(),
+        is_oceanic: false,
+        min: p,
+        max: p,
+        center: p,
+        points: vec![],
+      },
+      controllers: HashMap::new(),
+      country: None,
+    }
+  }
+
+  #[test]
+  fn test_check() {
+    let airports = vec![
+      airport("EGLL", "LHR", "EGTT", true),
+      airport("EGKK", "LGW", "EGXX", true),
+      airport("EGLC", "LHR", "EGTT", false),
+    ];
+    let firs = vec![fir("EGTT"), fir("EGPX")];
+    let uirs = vec![UIR::new(
+      "EGGX",
+      "Shanwick",
+      vec!["EGTT".into(), "EGGX".into()],
+    )];
+
+    let mut report = ValidationReport::default();
+    report.check(&airports, &firs, &uirs);
+    assert_eq!(
+      report.uirs_with_unknown_firs,
+      vec![UnknownFIRs {
+        uir: "EGGX".into(),
+        firs: vec!["EGGX".into()]
+      }]
+    );
+    assert_eq!(
+      report.airports_without_fir,
+      vec![AirportFIR {
+        airport: "EGKK".into(),
+        fir: "EGXX".into()
+      }]
+    );
+    assert_eq!(
+      report.duplicate_iata,
+      vec![DuplicateIATA {
+        iata: "LHR".into(),
+        airports: vec!["EGLL:LHR".into(), "EGLC:LHR".into()]
+      }]
+    );
+    assert_eq!(report.airports_without_runways, vec!["EGLC".to_owned()]);
+    assert_eq!(report.errors(), 1);
+    assert_eq!(report.warnings(), 3);
+    assert!(report.has_errors());
+    assert!(report.to_string().ends_with("1 errors, 3 warnings"));
+  }
+}
@@ -35,7 +35,7 @@ impl<'a> Lexer<'a> {
     loop {
       let sym = self.src.peek();
       if let Some(sym) = sym {
-        if ('0'..='9').contains(&sym) {
+        if sym.is_ascii_digit() {
           literal.push(sym);
         } else if sym == '.' {
           if !dot_met {
@@ -304,12 +304,12 @@ impl<'a> Lexer<'a> {
     }
   }
 
-  pub fn parse(&mut self) -> TokenFlow {
+  pub fn parse(&mut self) -> TokenFlow<'_> {
     loop {
       let sym = self.src.peek();
       if let Some(sym) = sym {
         let s = String::from(sym);
-        let token = if ('0'..='9').contains(&sym) {
+        let token = if sym.is_ascii_digit() {
           self.read_number()
         } else if IDENT_START.is_match(&s) {
           self.read_identifier()
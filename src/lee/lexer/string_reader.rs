@@ -59,8 +59,8 @@ pub mod tests {
     s.advance();
     assert!(s.peek() == Some('o'));
     s.advance();
-    assert!(s.peek() == None);
+    assert!(s.peek().is_none());
     s.advance();
-    assert!(s.peek() == None);
+    assert!(s.peek().is_none());
   }
 }
@@ -29,7 +29,7 @@ impl<T> Expression<T> {
       }
       LeftExpression::Condition(cond) => {
         let compiled = cb(cond.clone())?;
-        self.left = Box::new(LeftExpression::CompiledFilter(compiled));
+        *self.left = LeftExpression::CompiledFilter(compiled);
       }
       _ => (), // TODO: already compiled error
     }
@@ -48,14 +48,14 @@ impl<T> Expression<T> {
       _ => false, // TODO: partially compiled error
     };
 
-    if self.operator.is_none() {
-      left_result
-    } else {
+    if let Some(operator) = &self.operator {
       let right = self.right.as_ref().unwrap();
-      match self.operator.as_ref().unwrap() {
+      match operator {
         CombineOperator::And => left_result && right.evaluate(model),
         CombineOperator::Or => left_result || right.evaluate(model),
       }
+    } else {
+      left_result
     }
   }
 }
@@ -1,10 +1,21 @@
 #[macro_use]
 extern crate rocket;
 
-use camden::config::read_config;
-use camden::web::error::{catch404, catch500};
-use camden::web::{build_info, check_query, get_pilot, metrics, updates};
-use camden::{manager::Manager, web::get_airport};
+use camden::config::{read_config, Config as CamdenConfig};
+use camden::fixed::parser::load_fixed;
+use camden::web::admin::{
+  fixed_reload, fixed_validation, replay_pause, replay_resume, replay_seek, replay_speed,
+  replay_status, replay_step, unmatched_controllers,
+};
+use camden::web::error::{catch401, catch403, catch404, catch500};
+use camden::web::{
+  build_info, check_query, get_controllers_history, get_history_by_callsign, get_history_by_cid,
+  get_pilot, get_track, healthz, metrics, readyz, updates,
+};
+use camden::{
+  manager::Manager,
+  web::{get_airport, get_airport_board},
+};
 use log::{error, info};
 use rocket::config::Config as RocketConfig;
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
@@ -12,6 +23,26 @@ use std::sync::Arc;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// "camden validate" checks fixed data consistency and exits,
+// the exit code is 1 if there are errors and 2 if data can't be loaded
+async fn validate_fixed(config: &CamdenConfig) -> i32 {
+  match load_fixed(config, false).await {
+    Ok(fixed) => {
+      let report = fixed.validation();
+      println!("{report}");
+      if report.has_errors() {
+        1
+      } else {
+        0
+      }
+    }
+    Err(err) => {
+      error!("error loading fixed data: {err}");
+      2
+    }
+  }
+}
+
 #[launch]
 async fn rocket() -> _ {
   // TODO cmdline flag -c
@@ -25,9 +56,18 @@ async fn rocket() -> _ {
   )
   .unwrap();
 
+  if std::env::args().nth(1).as_deref() == Some("validate") {
+    std::process::exit(validate_fixed(&config).await);
+  }
+
   info!("starting camden server version {}", VERSION);
-  let m = Manager::new(config.clone()).await;
-  let m = Arc::new(m);
+  let m = match Manager::new(config.clone()).await {
+    Ok(m) => Arc::new(m),
+    Err(err) => {
+      error!("error setting vatsim data manager up: {err}");
+      std::process::exit(1);
+    }
+  };
 
   {
     let m = m.clone();
@@ -39,14 +79,39 @@ async fn rocket() -> _ {
     });
   }
 
+  {
+    let m = m.clone();
+    tokio::spawn(async move { m.run_fixed_reload().await });
+  }
+
   let figment = RocketConfig::figment().merge(("port", config.web.port));
 
   rocket::custom(figment)
     .manage(m)
     .mount(
       "/api",
-      routes![updates, get_airport, get_pilot, check_query, build_info],
+      routes![
+        updates,
+        get_airport,
+        get_airport_board,
+        get_pilot,
+        get_history_by_cid,
+        get_history_by_callsign,
+        get_track,
+        get_controllers_history,
+        check_query,
+        build_info,
+        replay_status,
+        replay_pause,
+        replay_resume,
+        replay_step,
+        replay_speed,
+        replay_seek,
+        fixed_reload,
+        fixed_validation,
+        unmatched_controllers
+      ],
     )
-    .mount("/", routes![metrics])
-    .register("/", catchers![catch404, catch500])
+    .mount("/", routes![metrics, healthz, readyz])
+    .register("/", catchers![catch401, catch403, catch404, catch500])
 }
@@ -0,0 +1,165 @@
+use crate::config::{Api as ApiConfig, FeedSourceType};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+/// FeedHealth keeps track of VATSIM feed fetches: failed attempts are
+/// retried with exponential backoff, and the feed is considered stale when
+/// general.updated_at hasn't advanced for a while even if the fetches succeed.
+#[derive(Debug)]
+pub struct FeedHealth {
+  retry_delay: Duration,
+  max_retry_delay: Duration,
+  max_failures: u32,
+  // replayed data is frozen on pause legitimately so it's never stale
+  stale_after: Option<chrono::Duration>,
+
+  consecutive_failures: u32,
+  total_failures: u64,
+  last_error: Option<String>,
+  last_success: Option<DateTime<Utc>>,
+  // the latest general.updated_at and the moment it was first seen
+  updated_at: Option<DateTime<Utc>>,
+  advanced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedStatus {
+  pub healthy: bool,
+  pub stale: bool,
+  pub consecutive_failures: u32,
+  pub last_error: Option<String>,
+  pub last_success: Option<DateTime<Utc>>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl FeedHealth {
+  pub fn new(cfg: &ApiConfig) -> Self {
+    Self {
+      retry_delay: cfg.retry_delay,
+      max_retry_delay: cfg.max_retry_delay,
+      max_failures: cfg.max_failures,
+      stale_after: match cfg.source {
+        FeedSourceType::Replay => None,
+        _ => Some(chrono::Duration::from_std(cfg.stale_after).unwrap()),
+      },
+      consecutive_failures: 0,
+      total_failures: 0,
+      last_error: None,
+      last_success: None,
+      updated_at: None,
+      advanced_at: None,
+    }
+  }
+
+  pub fn success(&mut self, updated_at: DateTime<Utc>, now: DateTime<Utc>) {
+    self.consecutive_failures = 0;
+    self.last_error = None;
+    self.last_success = Some(now);
+    if self.updated_at != Some(updated_at) {
+      self.updated_at = Some(updated_at);
+      self.advanced_at = Some(now);
+    }
+  }
+
+  pub fn failure(&mut self, err: &str) {
+    self.consecutive_failures += 1;
+    self.total_failures += 1;
+    self.last_error = Some(err.to_owned());
+  }
+
+  pub fn consecutive_failures(&self) -> u32 {
+    self.consecutive_failures
+  }
+
+  pub fn total_failures(&self) -> u64 {
+    self.total_failures
+  }
+
+  /// Delay before the next retry, None if the last fetch has succeeded
+  pub fn retry_in(&self) -> Option<Duration> {
+    if self.consecutive_failures == 0 {
+      return None;
+    }
+    let exp = (self.consecutive_failures - 1).min(16);
+    let delay = self.retry_delay.saturating_mul(1 << exp);
+    Some(delay.min(self.max_retry_delay))
+  }
+
+  pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+    match (self.advanced_at, self.stale_after) {
+      (Some(advanced_at), Some(stale_after)) => now - advanced_at > stale_after,
+      (Some(_), None) => false,
+      (None, _) => true,
+    }
+  }
+
+  pub fn is_healthy(&self, now: DateTime<Utc>) -> bool {
+    self.consecutive_failures < self.max_failures && !self.is_stale(now)
+  }
+
+  pub fn status(&self, now: DateTime<Utc>) -> FeedStatus {
+    FeedStatus {
+      healthy: self.is_healthy(now),
+      stale: self.is_stale(now),
+      consecutive_failures: self.consecutive_failures,
+      last_error: self.last_error.clone(),
+      last_success: self.last_success,
+      updated_at: self.updated_at,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_feed_health() {
+    let cfg = ApiConfig::default();
+    let mut health = FeedHealth::new(&cfg);
+    let now = Utc::now();
+    assert!(health.is_stale(now));
+    assert!(!health.is_healthy(now));
+    assert_eq!(health.retry_in(), None);
+
+    health.success(now, now);
+    assert!(health.is_healthy(now));
+
+    health.failure("timeout");
+    assert_eq!(health.retry_in(), Some(Duration::from_secs(1)));
+    health.failure("timeout");
+    assert_eq!(health.retry_in(), Some(Duration::from_secs(2)));
+    health.failure("timeout");
+    assert_eq!(health.retry_in(), Some(Duration::from_secs(4)));
+    assert!(!health.is_healthy(now));
+    for _ in 0..20 {
+      health.failure("timeout");
+    }
+    assert_eq!(health.retry_in(), Some(cfg.max_retry_delay));
+    assert_eq!(health.status(now).last_error.as_deref(), Some("timeout"));
+
+    // fetches succeed but the data doesn't change
+    let later = now + chrono::Duration::minutes(1);
+    health.success(now, later);
+    assert_eq!(health.retry_in(), None);
+    assert_eq!(health.total_failures(), 23);
+    assert!(health.is_healthy(later));
+    let later = now + chrono::Duration::minutes(3);
+    health.success(now, later);
+    assert!(health.is_stale(later));
+    assert!(!health.is_healthy(later));
+
+    health.success(later, later);
+    assert!(health.is_healthy(later));
+
+    let cfg = ApiConfig {
+      source: FeedSourceType::Replay,
+      ..Default::default()
+    };
+    let mut health = FeedHealth::new(&cfg);
+    assert!(health.is_stale(now));
+    health.success(now, now);
+    assert!(!health.is_stale(now + chrono::Duration::days(1)));
+  }
+}
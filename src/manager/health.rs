@@ -0,0 +1,78 @@
+use super::feed::FeedStatus;
+use crate::weather::WeatherStatus;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreStatus {
+  pub healthy: bool,
+  pub error: Option<String>,
+}
+
+/// Health breakdown of the instance. Weather is reported for information
+/// only, airports are simply shown without weather while it's unavailable.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+  pub ready: bool,
+  pub fixed_data_loaded: bool,
+  pub vatsim_data_age_sec: Option<i64>,
+  pub feed: FeedStatus,
+  // None if tracks are not stored
+  pub track_store: Option<StoreStatus>,
+  pub weather: WeatherStatus,
+}
+
+impl HealthReport {
+  pub fn new(
+    fixed_data_loaded: bool,
+    vatsim_data_age_sec: Option<i64>,
+    feed: FeedStatus,
+    track_store: Option<StoreStatus>,
+    weather: WeatherStatus,
+  ) -> Self {
+    let store_ok = track_store.as_ref().map(|s| s.healthy).unwrap_or(true);
+    Self {
+      ready: fixed_data_loaded && feed.healthy && store_ok,
+      fixed_data_loaded,
+      vatsim_data_age_sec,
+      feed,
+      track_store,
+      weather,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_readiness() {
+    let feed = FeedStatus {
+      healthy: true,
+      stale: false,
+      consecutive_failures: 0,
+      last_error: None,
+      last_success: None,
+      updated_at: None,
+    };
+    let weather = WeatherStatus {
+      healthy: false,
+      requests: 1,
+      cached_locations: 0,
+      last_success: None,
+      last_error: Some("timeout".into()),
+    };
+    let report = HealthReport::new(true, Some(10), feed.clone(), None, weather.clone());
+    assert!(report.ready);
+
+    let report = HealthReport::new(false, Some(10), feed.clone(), None, weather.clone());
+    assert!(!report.ready);
+
+    let store = StoreStatus {
+      healthy: false,
+      error: Some("ping timed out".into()),
+    };
+    let report = HealthReport::new(true, Some(10), feed, Some(store), weather);
+    assert!(!report.ready);
+  }
+}
@@ -1,7 +1,8 @@
 use chrono::{DateTime, Utc};
 use std::{collections::HashMap, fmt::Display};
 
-use crate::seconds_since;
+use super::{feed::FeedHealth, UnmatchedController};
+use crate::{persistent::CleanupStats, seconds_since, util::Counter};
 
 #[macro_export]
 macro_rules! labels {
@@ -108,6 +109,14 @@ pub struct Metrics {
   pub vatsim_data_load_time_sec: Metric<f32>,
   pub processing_time_sec: Metric<f32>,
   pub db_cleanup_time_sec: Metric<f32>,
+  pub db_cleanup_deleted_objects: Metric<u64>,
+  pub track_points_skipped: Metric<u64>,
+  pub track_points_dropped: Metric<u64>,
+  pub vatsim_feed_errors: Metric<u64>,
+  pub vatsim_feed_consecutive_failures: Metric<u32>,
+  pub vatsim_feed_stale: Metric<u8>,
+  pub vatsim_feed_healthy: Metric<u8>,
+  pub vatsim_controllers_unmatched: Metric<usize>,
   pub process_started_at: DateTime<Utc>,
 }
 
@@ -145,10 +154,90 @@ impl Metrics {
         "Time spent cleaning up database stored objects",
         MetricType::Gauge,
       ),
+      db_cleanup_deleted_objects: Metric::new(
+        "db_cleanup_deleted_objects",
+        "Number of objects deleted by the latest database cleanup",
+        MetricType::Gauge,
+      ),
+      track_points_skipped: Metric::new(
+        "track_points_skipped",
+        "Track points not stored as the pilot hasn't moved or changed state",
+        MetricType::Counter,
+      ),
+      track_points_dropped: Metric::new(
+        "track_points_dropped",
+        "Track points dropped as the track writer queue was full or the store kept failing",
+        MetricType::Counter,
+      ),
+      vatsim_feed_errors: Metric::new(
+        "vatsim_feed_errors",
+        "Failed Vatsim data fetches",
+        MetricType::Counter,
+      ),
+      vatsim_feed_consecutive_failures: Metric::new(
+        "vatsim_feed_consecutive_failures",
+        "Vatsim data fetches failed in a row",
+        MetricType::Gauge,
+      ),
+      vatsim_feed_stale: Metric::new(
+        "vatsim_feed_stale",
+        "1 if Vatsim data timestamp hasn't advanced for too long",
+        MetricType::Gauge,
+      ),
+      vatsim_feed_healthy: Metric::new(
+        "vatsim_feed_healthy",
+        "1 if Vatsim data is being fetched and kept up to date",
+        MetricType::Gauge,
+      ),
+      vatsim_controllers_unmatched: Metric::new(
+        "vatsim_controllers_unmatched",
+        "Online controllers not attached to any airport, FIR or TRACON",
+        MetricType::Gauge,
+      ),
       process_started_at: Utc::now(),
     }
   }
 
+  pub fn set_cleanup_stats(&mut self, stats: &CleanupStats, process_time: f32) {
+    self.db_cleanup_time_sec.set_single(process_time);
+    self
+      .db_cleanup_deleted_objects
+      .set(labels!("object_type" = "track"), stats.tracks);
+    self
+      .db_cleanup_deleted_objects
+      .set(labels!("object_type" = "trackpoint"), stats.track_points);
+    self.db_cleanup_deleted_objects.set(
+      labels!("object_type" = "controller_session"),
+      stats.controller_sessions,
+    );
+  }
+
+  pub fn set_unmatched_controllers(&mut self, unmatched: &[UnmatchedController]) {
+    let mut grouped = Counter::new();
+    for ctrl in unmatched {
+      grouped.inc(ctrl.facility.to_string());
+    }
+    self.vatsim_controllers_unmatched.reset();
+    for (facility, count) in grouped.iter() {
+      self
+        .vatsim_controllers_unmatched
+        .set(labels!("controller_type" = facility), *count);
+    }
+  }
+
+  pub fn set_feed_health(&mut self, health: &FeedHealth, now: DateTime<Utc>) {
+    self.vatsim_feed_errors.set_single(health.total_failures());
+    self
+      .vatsim_feed_consecutive_failures
+      .set_single(health.consecutive_failures());
+    self
+      .vatsim_feed_stale
+      .set_single(health.is_stale(now) as u8);
+    self
+      .vatsim_feed_healthy
+      .set_single(health.is_healthy(now) as u8);
+  }
+
   pub fn render(&self) -> String {
     let t = Utc::now().timestamp();
     let mut metrics = vec![];
@@ -168,6 +257,14 @@ impl Metrics {
 
     metrics.push(self.vatsim_data_load_time_sec.render());
     metrics.push(self.db_cleanup_time_sec.render());
+    metrics.push(self.db_cleanup_deleted_objects.render());
+    metrics.push(self.track_points_skipped.render());
+    metrics.push(self.track_points_dropped.render());
+    metrics.push(self.vatsim_feed_errors.render());
+    metrics.push(self.vatsim_feed_consecutive_failures.render());
+    metrics.push(self.vatsim_feed_stale.render());
+    metrics.push(self.vatsim_feed_healthy.render());
+    metrics.push(self.vatsim_controllers_unmatched.render());
 
     let mut metric = Metric::new("uptime", "Process uptime in sec", MetricType::Counter);
     let sec = seconds_since(self.process_started_at).ceil() as u64;
@@ -1,44 +1,103 @@
+pub mod feed;
+pub mod health;
 pub mod metrics;
+pub mod movements;
+pub mod phase;
+pub mod sessions;
 pub mod spatial;
 
 use self::{
+  feed::{FeedHealth, FeedStatus},
+  health::{HealthReport, StoreStatus},
   metrics::Metrics,
+  movements::{AirportBoard, BoardFlight, MovementKind, MovementLog},
+  phase::{nearby_field, PhaseTracker},
+  sessions::SessionTracker,
   spatial::{PointObject, RectObject},
 };
 use crate::{
-  config::Config,
+  config::{Config, FeedSourceType, Track as TrackConfig, TrackBackend},
   fixed::{
     data::FixedData,
     parser::load_fixed,
-    types::{Airport, FIR},
+    types::{Airport, FIR, TRACON, UIR},
+    validate::ValidationReport,
   },
   labels,
   moving::{
     controller::{Controller, Facility},
     load_vatsim_data,
-    pilot::Pilot,
+    pilot::{FlightPhase, FlightProgress, Pilot},
+    recorder::FeedRecorder,
+    replay::ReplaySource,
+    source::{create_source, FeedError, FeedSource},
+  },
+  persistent::{
+    self, detect::ChangeDetector, writer::TrackWriter, ControllerSession, SessionQuery, StoreError,
+    TrackInfo, TrackPoint, TrackQuery, TrackStore,
   },
-  persistent::{Persistent, TrackPoint},
   seconds_since,
   types::Rect,
   util::Counter,
   weather::WeatherManager,
 };
 use chrono::{Duration, Utc};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use rstar::RTree;
+use serde::Serialize;
 use std::{
   collections::{HashMap, HashSet},
-  sync::Arc,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
+use tokio::{
+  sync::RwLock,
+  time::{sleep, timeout},
 };
-use tokio::{sync::RwLock, time::sleep};
 
 const CLEANUP_EVERY_X_ITER: u8 = 5;
+const STORE_PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixedDataStats {
+  pub airports: usize,
+  pub firs: usize,
+  pub uirs: usize,
+  pub tracons: usize,
+  // online controllers re-attached to the new data
+  pub controllers: usize,
+}
+
+/// Online controller which couldn't be attached to any airport,
+/// FIR or TRACON, usually because of a non-standard callsign
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmatchedController {
+  pub callsign: String,
+  pub cid: u32,
+  pub name: String,
+  pub facility: Facility,
+  pub freq: u32,
+}
+
+impl From<&Controller> for UnmatchedController {
+  fn from(ctrl: &Controller) -> Self {
+    Self {
+      callsign: ctrl.callsign.clone(),
+      cid: ctrl.cid,
+      name: ctrl.name.clone(),
+      facility: ctrl.facility.clone(),
+      freq: ctrl.freq,
+    }
+  }
+}
 
 #[derive(Debug)]
 pub struct Manager {
   cfg: Config,
   fixed: RwLock<FixedData>,
+  fixed_loaded: AtomicBool,
 
   pilots: RwLock<HashMap<String, Pilot>>,
   pilots2d: RwLock<RTree<PointObject>>,
@@ -46,26 +105,49 @@ pub struct Manager {
 
   airports2d: RwLock<RTree<PointObject>>,
   firs2d: RwLock<RTree<RectObject>>,
-  db: Option<RwLock<Persistent>>,
+  tracons2d: RwLock<RTree<RectObject>>,
+  uirs2d: RwLock<RTree<RectObject>>,
+  // online controllers, kept to re-attach them when fixed data is reloaded
+  controllers: RwLock<HashMap<String, Controller>>,
+  unmatched_controllers: RwLock<Vec<UnmatchedController>>,
+  // recent takeoffs and landings by airport
+  movements: RwLock<MovementLog>,
+  movements_max_age: Duration,
+  db: Option<Arc<dyn TrackStore>>,
+  track_writer: Option<TrackWriter>,
+  feed: Box<dyn FeedSource>,
+  feed_health: RwLock<FeedHealth>,
+  wx_manager: Arc<WeatherManager>,
 
   metrics: RwLock<Metrics>,
 }
 
 impl Manager {
-  pub async fn new(cfg: Config) -> Self {
+  pub async fn new(cfg: Config) -> Result<Self, FeedError> {
     info!("setting vatsim data manager up");
 
-    let res = Persistent::new(&cfg).await;
+    // replayed pilots and controllers must not end up in the real
+    // flight and controller history, they're kept in memory instead
+    let track_cfg = if cfg.api.source == FeedSourceType::Replay {
+      info!("replaying vatsim data, using the memory track store");
+      TrackConfig {
+        backend: TrackBackend::Memory,
+        ..cfg.track.clone()
+      }
+    } else {
+      cfg.track.clone()
+    };
+    let res = persistent::connect(&track_cfg).await;
 
     if let Err(err) = &res {
       error!("error creating track store: {}", err)
     }
 
-    let persistent = res.ok().map(RwLock::new);
+    let persistent: Option<Arc<dyn TrackStore>> = res.ok().map(Arc::from);
+    let mut metrics = Metrics::new();
 
     if let Some(persistent) = &persistent {
       info!("creating database indices");
-      let persistent = persistent.write().await;
 
       let res = persistent.indexes().await;
       if let Err(err) = res {
@@ -75,31 +157,103 @@ impl Manager {
       info!("cleaning up tracks");
       let t = Utc::now();
       let res = persistent.cleanup().await;
-      if let Err(err) = res {
-        error!("error cleaning up: {}", err);
-      } else {
-        let process_time = seconds_since(t);
-        info!("boot-time db cleanup took {process_time}s");
+      match res {
+        Err(err) => error!("error cleaning up: {}", err),
+        Ok(stats) => {
+          let process_time = seconds_since(t);
+          info!("boot-time db cleanup took {process_time}s");
+          metrics.set_cleanup_stats(&stats, process_time);
+        }
       }
     }
 
-    Self {
+    let track_writer = persistent
+      .as_ref()
+      .map(|persistent| TrackWriter::spawn(persistent.clone(), ChangeDetector::new(&cfg.track)));
+
+    let movements_max_age =
+      Duration::from_std(cfg.camden.movements_max_age).unwrap_or_else(|err| {
+        error!(
+          "invalid movements_max_age {:?}: {err}, using 2h",
+          cfg.camden.movements_max_age
+        );
+        Duration::hours(2)
+      });
+
+    info!("using {:?} vatsim feed source", cfg.api.source);
+    let feed = create_source(&cfg.api)?;
+    let feed_health = FeedHealth::new(&cfg.api);
+    // TODO: configurable weather ttl
+    let wx_manager = WeatherManager::new(Duration::seconds(1800));
+
+    Ok(Self {
       cfg,
       fixed: RwLock::new(FixedData::empty()),
+      fixed_loaded: AtomicBool::new(false),
       pilots: RwLock::new(HashMap::new()),
       pilots2d: RwLock::new(RTree::new()),
       pilots_po: RwLock::new(HashMap::new()),
       airports2d: RwLock::new(RTree::new()),
       firs2d: RwLock::new(RTree::new()),
+      tracons2d: RwLock::new(RTree::new()),
+      uirs2d: RwLock::new(RTree::new()),
+      controllers: RwLock::new(HashMap::new()),
+      unmatched_controllers: RwLock::new(vec![]),
+      movements: RwLock::new(MovementLog::new()),
+      movements_max_age,
       db: persistent,
-      metrics: RwLock::new(Metrics::new()),
-    }
+      track_writer,
+      feed,
+      feed_health: RwLock::new(feed_health),
+      wx_manager: Arc::new(wx_manager),
+      metrics: RwLock::new(metrics),
+    })
   }
 
   pub fn config(&self) -> &Config {
     &self.cfg
   }
 
+  pub fn replay(&self) -> Option<&ReplaySource> {
+    self.feed.replay()
+  }
+
+  pub async fn feed_status(&self) -> FeedStatus {
+    self.feed_health.read().await.status(Utc::now())
+  }
+
+  pub async fn health(&self) -> HealthReport {
+    let fixed_data_loaded = self.fixed_loaded.load(Ordering::Relaxed);
+    let ts = self.metrics.read().await.vatsim_data_timestamp;
+    let vatsim_data_age_sec = if ts > 0 {
+      Some(Utc::now().timestamp() - ts)
+    } else {
+      None
+    };
+    let track_store = match &self.db {
+      Some(tracks) => {
+        let res = timeout(STORE_PING_TIMEOUT, tracks.ping()).await;
+        let error = match res {
+          Ok(Ok(())) => None,
+          Ok(Err(err)) => Some(err.to_string()),
+          Err(_) => Some("ping timed out".to_owned()),
+        };
+        Some(StoreStatus {
+          healthy: error.is_none(),
+          error,
+        })
+      }
+      None => None,
+    };
+    HealthReport::new(
+      fixed_data_loaded,
+      vatsim_data_age_sec,
+      self.feed_status().await,
+      track_store,
+      self.wx_manager.status().await,
+    )
+  }
+
   pub async fn render_metrics(&self) -> String {
     self.metrics.read().await.render()
   }
@@ -129,6 +283,26 @@ impl Manager {
       .collect()
   }
 
+  pub async fn get_all_tracons(&self) -> Vec<TRACON> {
+    let fixed = self.fixed.read().await;
+    fixed
+      .tracons()
+      .iter()
+      .filter(|tracon| !tracon.is_empty())
+      .cloned()
+      .collect()
+  }
+
+  pub async fn get_all_uirs(&self) -> Vec<UIR> {
+    let fixed = self.fixed.read().await;
+    fixed
+      .uirs()
+      .iter()
+      .filter(|uir| !uir.is_empty())
+      .cloned()
+      .collect()
+  }
+
   pub async fn get_pilots(&self, rect: &Rect) -> Vec<Pilot> {
     let pilots2d = self.pilots2d.read().await;
     let pilots_idx = self.pilots.read().await;
@@ -179,24 +353,185 @@ impl Manager {
     firs.into_values().collect()
   }
 
+  pub async fn get_tracons(&self, rect: &Rect) -> Vec<TRACON> {
+    let tracons2d = self.tracons2d.read().await;
+    let fixed = self.fixed.read().await;
+    let mut tracons = HashMap::new();
+
+    for env in rect.envelopes() {
+      for po in tracons2d.locate_in_envelope_intersecting(&env) {
+        if let Some(tracon) = fixed.find_tracon(&po.id) {
+          if !tracon.is_empty() {
+            tracons.insert(po.id.clone(), tracon.clone());
+          }
+        }
+      }
+    }
+    tracons.into_values().collect()
+  }
+
+  pub async fn get_uirs(&self, rect: &Rect) -> Vec<UIR> {
+    let uirs2d = self.uirs2d.read().await;
+    let fixed = self.fixed.read().await;
+    let mut uirs = HashMap::new();
+
+    for env in rect.envelopes() {
+      for po in uirs2d.locate_in_envelope_intersecting(&env) {
+        if let Some(uir) = fixed.find_uir(&po.id) {
+          if !uir.is_empty() {
+            uirs.insert(po.id.clone(), uir.clone());
+          }
+        }
+      }
+    }
+    uirs.into_values().collect()
+  }
+
+  pub async fn fixed_validation(&self) -> ValidationReport {
+    self.fixed.read().await.validation().clone()
+  }
+
   pub async fn find_airport(&self, code: &str) -> Option<Airport> {
     self.fixed.read().await.find_airport(code)
   }
 
   async fn setup_fixed_data(&self) -> Result<(), Box<dyn std::error::Error>> {
     info!("loading fixed data");
-    let fixed = load_fixed(&self.cfg).await?; // TODO retries
-    for arpt in fixed.airports() {
-      self.airports2d.write().await.insert(arpt.into());
-    }
-    for fir in fixed.firs() {
-      self.firs2d.write().await.insert(fir.into())
-    }
-    self.fixed.write().await.fill(fixed);
+    let fixed = load_fixed(&self.cfg, false).await?; // TODO retries
+    self.install_fixed_data(fixed).await;
     info!("fixed data configured");
     Ok(())
   }
 
+  /// Loads fixed data from scratch and replaces the current one along with
+  /// the spatial indexes. Online controllers and weather are attached to the
+  /// new data before the swap so clients never see airports and FIRs
+  /// without their controllers.
+  pub async fn reload_fixed_data(&self) -> Result<FixedDataStats, Box<dyn std::error::Error>> {
+    info!("reloading fixed data");
+    let t = Utc::now();
+    let fixed = load_fixed(&self.cfg, true).await?;
+    let stats = self.install_fixed_data(fixed).await;
+    info!(
+      "fixed data reloaded in {}s: {} airports, {} firs, {} uirs, {} tracons, {} controllers re-attached",
+      seconds_since(t),
+      stats.airports,
+      stats.firs,
+      stats.uirs,
+      stats.tracons,
+      stats.controllers
+    );
+    Ok(stats)
+  }
+
+  async fn install_fixed_data(&self, mut fixed: FixedData) -> FixedDataStats {
+    let validation = fixed.validation();
+    if validation.has_errors() {
+      warn!(
+        "fixed data validation: {} errors, {} warnings",
+        validation.errors(),
+        validation.warnings()
+      );
+    }
+
+    // controllers stay locked until the swap so the main loop can't
+    // change them meanwhile
+    let controllers = self.controllers.read().await;
+    let mut controlled_arpt = HashSet::new();
+    let mut unmatched = vec![];
+    for ctrl in controllers.values() {
+      match ctrl.facility {
+        Facility::Radar => {
+          if fixed.set_fir_controller(ctrl.clone()).is_none()
+            && fixed.set_uir_controller(ctrl.clone()).is_none()
+          {
+            unmatched.push(ctrl.into());
+          }
+        }
+        _ => {
+          let in_tracon = ctrl.facility == Facility::Approach
+            && fixed.set_tracon_controller(ctrl.clone()).is_some();
+          if let Some(arpt) = fixed.set_airport_controller(ctrl.clone()) {
+            controlled_arpt.insert(arpt.icao.clone());
+          } else if !in_tracon {
+            unmatched.push(ctrl.into());
+          }
+        }
+      }
+    }
+    for icao in controlled_arpt.iter() {
+      if let Some(wx) = self.wx_manager.get(icao).await {
+        fixed.set_airport_weather(icao, wx);
+      }
+    }
+
+    let airports = fixed.airports().iter().map(|arpt| arpt.into()).collect();
+    let firs = fixed.firs().iter().flat_map(RectObject::from_fir).collect();
+    let tracons = fixed
+      .tracons()
+      .iter()
+      .flat_map(RectObject::from_tracon)
+      .collect();
+    let uirs = fixed
+      .uirs()
+      .iter()
+      .filter(|uir| !uir.points.is_empty())
+      .flat_map(RectObject::from_uir)
+      .collect();
+    let stats = FixedDataStats {
+      airports: fixed.airports().len(),
+      firs: fixed.firs().len(),
+      uirs: fixed.uirs().len(),
+      tracons: fixed.tracons().len(),
+      controllers: controllers.len(),
+    };
+
+    // the same locking order as in get_airports(), get_firs(), get_tracons()
+    // and get_uirs()
+    let mut airports2d = self.airports2d.write().await;
+    let mut firs2d = self.firs2d.write().await;
+    let mut tracons2d = self.tracons2d.write().await;
+    let mut uirs2d = self.uirs2d.write().await;
+    let mut current = self.fixed.write().await;
+    *airports2d = RTree::bulk_load(airports);
+    *firs2d = RTree::bulk_load(firs);
+    *tracons2d = RTree::bulk_load(tracons);
+    *uirs2d = RTree::bulk_load(uirs);
+    current.fill(fixed);
+    self.fixed_loaded.store(true, Ordering::Relaxed);
+    self.set_unmatched_controllers(unmatched).await;
+    stats
+  }
+
+  pub async fn get_unmatched_controllers(&self) -> Vec<UnmatchedController> {
+    self.unmatched_controllers.read().await.clone()
+  }
+
+  async fn set_unmatched_controllers(&self, mut unmatched: Vec<UnmatchedController>) {
+    unmatched.sort_by(|a, b| a.callsign.cmp(&b.callsign));
+    self
+      .metrics
+      .write()
+      .await
+      .set_unmatched_controllers(&unmatched);
+    *self.unmatched_controllers.write().await = unmatched;
+  }
+
+  /// Reloads fixed data every reload_period, meant to be spawned
+  /// along with run()
+  pub async fn run_fixed_reload(&self) {
+    let period = self.cfg.fixed.reload_period;
+    if period.is_zero() {
+      return;
+    }
+    loop {
+      sleep(period).await;
+      if let Err(err) = self.reload_fixed_data().await {
+        error!("error reloading fixed data: {err}");
+      }
+    }
+  }
+
   async fn remove_pilot(&self, callsign: &str) -> bool {
     let po = { self.pilots_po.write().await.remove(callsign) };
     if let Some(po) = po {
@@ -212,20 +547,28 @@ impl Manager {
     self.setup_fixed_data().await?;
 
     let mut pilots_callsigns = HashSet::new();
-    let mut controllers: HashMap<String, Controller> = HashMap::new();
     let mut data_updated_at = 0;
     let mut cleanup = CLEANUP_EVERY_X_ITER;
+    let mut sessions = SessionTracker::new();
+    let mut phases = PhaseTracker::new();
+    let recorder = if self.cfg.recorder.enabled && self.cfg.api.source == FeedSourceType::Replay {
+      warn!("recorder is disabled while replaying vatsim data");
+      None
+    } else if self.cfg.recorder.enabled {
+      info!("recording vatsim data to {}", self.cfg.recorder.dir);
+      Some(FeedRecorder::new(&self.cfg.recorder))
+    } else {
+      None
+    };
 
-    // TODO: configurable weather ttl
-    let wx_manager = WeatherManager::new(Duration::seconds(1800));
-    let wx_manager = Arc::new(wx_manager);
+    let wx_manager = self.wx_manager.clone();
     let wx_move = wx_manager.clone();
     tokio::spawn(async move { wx_move.run().await });
 
     loop {
       info!("loading vatsim data");
       let t = Utc::now();
-      let data = load_vatsim_data(&self.cfg).await;
+      let data = load_vatsim_data(self.feed.as_ref()).await;
       let process_time = seconds_since(t);
       self
         .metrics
@@ -234,11 +577,46 @@ impl Manager {
         .vatsim_data_load_time_sec
         .set_single(process_time);
       info!("vatsim data loaded in {}s", process_time);
-      if let Some(data) = data {
-        let ts = data.general.updated_at.timestamp();
-        if ts > data_updated_at {
+      let data = match data {
+        Ok(data) => Some(data),
+        Err(err) => {
+          error!("error loading vatsim data: {err}");
+          self.feed_health.write().await.failure(&err.to_string());
+          None
+        }
+      };
+      if let Some((data, raw)) = data {
+        self
+          .feed_health
+          .write()
+          .await
+          .success(data.general.updated_at, Utc::now());
+        // feed time rather than wall-clock time, they differ in replay
+        let feed_time = data.general.updated_at;
+        let ts = feed_time.timestamp();
+        // replay can go back in time when seeking
+        let is_replay = self.feed.replay().is_some();
+        if ts > data_updated_at || (is_replay && ts != data_updated_at) {
+          if ts < data_updated_at {
+            // takeoffs, landings and phases seen so far are in the future now
+            info!("vatsim data went back in time, resetting flight phases and movements");
+            phases = PhaseTracker::new();
+            *self.movements.write().await = MovementLog::new();
+          }
           data_updated_at = ts;
           self.metrics.write().await.vatsim_data_timestamp = ts;
+
+          if let Some(recorder) = &recorder {
+            let recorder = recorder.clone();
+            let updated_at = data.general.updated_at;
+            tokio::spawn(async move {
+              let res = recorder.record(raw, updated_at).await;
+              if let Err(err) = res {
+                error!("error recording vatsim data: {err}");
+              }
+            });
+          }
+
           // region:pilots_processing
           let mut fresh_pilots_callsigns = HashSet::new();
 
@@ -258,16 +636,10 @@ impl Manager {
 
               let po: PointObject = (&pilot).into();
 
-              let mut pilots2d = self.pilots2d.write().await;
-              let mut pilots_po = self.pilots_po.write().await;
-              let mut pilots = self.pilots.write().await;
-
-              // tracking first, to avoid additional cloning while inserting into hashmap later
-              if let Some(tracks) = &self.db {
-                let res = tracks.write().await.store_track(&pilot).await;
-                if let Err(err) = res {
-                  error!("error storing pilot track: {}", err);
-                }
+              // track points are only queued here, the writer stores them
+              // in background so the indexes are not locked meanwhile
+              if let Some(writer) = &self.track_writer {
+                writer.push(&pilot);
               }
 
               let country = self
@@ -279,6 +651,10 @@ impl Manager {
                 pilots_grouped.inc(country.geoname_id);
               }
 
+              let mut pilots2d = self.pilots2d.write().await;
+              let mut pilots_po = self.pilots_po.write().await;
+              let mut pilots = self.pilots.write().await;
+
               // We have to keep point objects in both hashmap and rtree
               // because rtree doesn't support searching by id:
               //
@@ -308,6 +684,10 @@ impl Manager {
             metrics
               .processing_time_sec
               .set(labels!("object_type" = "pilot"), process_time);
+            if let Some(writer) = &self.track_writer {
+              metrics.track_points_skipped.set_single(writer.skipped());
+              metrics.track_points_dropped.set_single(writer.dropped());
+            }
 
             let fixed = self.fixed.read().await;
             for (geo_id, count) in pilots_grouped.iter() {
@@ -332,6 +712,8 @@ impl Manager {
           let mut ccount = 0;
           let mut ctrl_grouped = Counter::new();
           let mut controlled_arpt = HashSet::new();
+          let mut unmatched = vec![];
+          let mut controllers = self.controllers.write().await;
           {
             let mut fixed = self.fixed.write().await;
 
@@ -342,19 +724,43 @@ impl Manager {
                 }
                 Facility::Radar => {
                   fresh_controllers.insert(ctrl.callsign.clone(), ctrl.clone());
-                  let fir = fixed.set_fir_controller(ctrl);
-                  if let Some(fir) = fir {
-                    let country = fir.country.as_ref();
-                    if let Some(country) = country {
-                      let key = format!("{}:radar", country.geoname_id);
-                      ctrl_grouped.inc(key);
-                    }
+                  let mut session = ControllerSession::new(&ctrl, None, None);
+                  let unmatched_ctrl: UnmatchedController = (&ctrl).into();
+                  // UIR controllers stay on the UIR, session.fir holds the UIR code then
+                  let fir = fixed.set_fir_controller(ctrl.clone());
+                  let (position, country) = if let Some(fir) = fir {
+                    (Some(fir.icao), fir.country)
+                  } else if let Some(uir) = fixed.set_uir_controller(ctrl) {
+                    (Some(uir.icao.clone()), uir.country.clone())
+                  } else {
+                    unmatched.push(unmatched_ctrl);
+                    (None, None)
+                  };
+                  session.fir = position;
+                  sessions.seen(session);
+                  if let Some(country) = country {
+                    let key = format!("{}:radar", country.geoname_id);
+                    ctrl_grouped.inc(key);
                   }
                 }
                 _ => {
                   fresh_controllers.insert(ctrl.callsign.clone(), ctrl.clone());
                   let facility = ctrl.facility.clone();
+                  let mut session = ControllerSession::new(&ctrl, None, None);
+                  let unmatched_ctrl: UnmatchedController = (&ctrl).into();
+                  let in_tracon = facility == Facility::Approach
+                    && fixed.set_tracon_controller(ctrl.clone()).is_some();
                   let arpt = fixed.set_airport_controller(ctrl);
+                  if arpt.is_none() && !in_tracon {
+                    unmatched.push(unmatched_ctrl);
+                  }
+                  if let Some(arpt) = arpt {
+                    session.airport = Some(arpt.icao.clone());
+                    if !arpt.fir_id.is_empty() {
+                      session.fir = Some(arpt.fir_id.clone());
+                    }
+                  }
+                  sessions.seen(session);
                   if let Some(arpt) = arpt {
                     controlled_arpt.insert(arpt.icao.clone());
                     let country = arpt.country.as_ref();
@@ -382,14 +788,42 @@ impl Manager {
           for (cs, ctrl) in controllers.iter() {
             if !fresh_controllers.contains_key(cs) {
               match ctrl.facility {
-                Facility::Radar => self.fixed.write().await.reset_fir_controller(ctrl),
+                Facility::Radar => {
+                  let mut fixed = self.fixed.write().await;
+                  fixed.reset_fir_controller(ctrl);
+                  fixed.reset_uir_controller(ctrl);
+                }
                 _ => {
-                  self.fixed.write().await.reset_airport_controller(ctrl);
+                  let mut fixed = self.fixed.write().await;
+                  if ctrl.facility == Facility::Approach {
+                    fixed.reset_tracon_controller(ctrl);
+                  }
+                  fixed.reset_airport_controller(ctrl);
+                }
+              }
+            }
+          }
+          sessions.close_missing(|cs| fresh_controllers.contains_key(cs), feed_time);
+          *controllers = fresh_controllers;
+          drop(controllers);
+          self.set_unmatched_controllers(unmatched).await;
+
+          let changed_sessions = sessions.take_changed();
+          if let Some(tracks) = &self.db {
+            if !changed_sessions.is_empty() {
+              let count = changed_sessions.len();
+              let res = tracks
+                .store_controller_sessions(changed_sessions.clone())
+                .await;
+              match res {
+                Ok(_) => debug!("{count} controller sessions stored"),
+                Err(err) => {
+                  error!("error storing controller sessions, retrying next time: {err}");
+                  sessions.put_back(changed_sessions);
                 }
               }
             }
           }
-          controllers = fresh_controllers;
 
           let process_time = seconds_since(t);
           {
@@ -416,11 +850,54 @@ impl Manager {
           }
           info!("{} controllers processed in {}s", ccount, process_time);
           // endregion:controllers_processing
+
+          // region:pilots_location
+          // done once controllers are set up so the controlling
+          // stations are up to date
+          {
+            let fixed = self.fixed.read().await;
+            let mut pilots = self.pilots.write().await;
+            for pilot in pilots.values_mut() {
+              let (fir, controlled_by) = fixed.locate(pilot.position);
+              pilot.fir = fir;
+              pilot.controlled_by = controlled_by;
+              let field = nearby_field(pilot, |code| fixed.airport_elevation(code));
+              pilot.phase = Some(phases.update(pilot, field));
+              pilot.progress = pilot.flight_plan.as_ref().and_then(|fp| {
+                let dep = fixed.find_airport_idx(&fp.departure)?;
+                let arr = fixed.find_airport_idx(&fp.arrival)?;
+                let airports = fixed.airports();
+                Some(FlightProgress::new(
+                  pilot,
+                  airports[dep].position,
+                  airports[arr].position,
+                ))
+              });
+            }
+            phases.forget_missing(|cs| pilots.contains_key(cs));
+          }
+          {
+            let mut movements = self.movements.write().await;
+            for movement in phases.take_movements() {
+              info!(
+                "{} {:?} at {} runway {}",
+                movement.callsign,
+                movement.kind,
+                movement.airport,
+                movement.runway.as_deref().unwrap_or("unknown")
+              );
+              movements.push(movement);
+            }
+            // relative to the feed time as movements carry pilot report
+            // times which lag behind the wall clock in replay
+            movements.expire(self.movements_max_age, feed_time);
+          }
+          // endregion:pilots_location
         }
 
         if let Some(tracks) = &self.db {
           let t = Utc::now();
-          let res = tracks.read().await.counters().await;
+          let res = tracks.counters().await;
           let process_time = seconds_since(t);
           match res {
             Ok((tc, tpc)) => {
@@ -440,26 +917,94 @@ impl Manager {
             }
           }
 
-          cleanup -= 1;
-          if cleanup == 0 {
+          if tracks.expires_automatically() {
+            // nothing to do, the store drops outdated tracks itself
+          } else if cleanup <= 1 {
             let t = Utc::now();
-            let res = tracks.write().await.cleanup().await;
+            let res = tracks.cleanup().await;
             match res {
               Err(err) => error!("error cleaning up db: {err}"),
-              Ok(_) => {
+              Ok(stats) => {
                 let process_time = seconds_since(t);
                 info!("db cleanup took {process_time}s");
+                self
+                  .metrics
+                  .write()
+                  .await
+                  .set_cleanup_stats(&stats, process_time);
                 cleanup = CLEANUP_EVERY_X_ITER;
               }
             }
           } else {
+            cleanup -= 1;
             debug!("{cleanup} iterations to db cleanup");
           }
         }
+      }
+
+      let retry_in = {
+        let health = self.feed_health.read().await;
+        self
+          .metrics
+          .write()
+          .await
+          .set_feed_health(&health, Utc::now());
+        health.retry_in()
+      };
+
+      match retry_in {
+        Some(delay) => {
+          info!("retrying vatsim data load in {delay:?}");
+          sleep(delay).await;
+        }
+        None => self.feed.wait(self.cfg.api.poll_period).await,
+      }
+    }
+  }
 
-        sleep(self.cfg.api.poll_period).await;
+  /// Recent takeoffs and landings at the airport along with the pilots
+  /// which are about to depart from or arrive to it
+  pub async fn get_airport_board(&self, code: &str) -> Option<AirportBoard> {
+    let fixed = self.fixed.read().await;
+    let idx = fixed.find_airport_idx(code)?;
+    let arpt = &fixed.airports()[idx];
+    let mut inbound = vec![];
+    let mut outbound = vec![];
+    {
+      let pilots = self.pilots.read().await;
+      for pilot in pilots.values() {
+        let (fp, phase) = match (&pilot.flight_plan, pilot.phase) {
+          (Some(fp), Some(phase)) => (fp, phase),
+          _ => continue,
+        };
+        if phase.is_airborne() && fixed.find_airport_idx(&fp.arrival) == Some(idx) {
+          let eta = pilot.progress.as_ref().and_then(|progress| progress.eta);
+          inbound.extend(BoardFlight::new(pilot, eta));
+        } else if !phase.is_airborne()
+          && phase != FlightPhase::Landed
+          && fixed.find_airport_idx(&fp.departure) == Some(idx)
+        {
+          outbound.extend(BoardFlight::new(pilot, None));
+        }
       }
     }
+    // the closest arrivals first, the ones without an ETA go last
+    inbound.sort_by(|a, b| match (a.eta, b.eta) {
+      (Some(a), Some(b)) => a.cmp(&b),
+      (Some(_), None) => std::cmp::Ordering::Less,
+      (None, Some(_)) => std::cmp::Ordering::Greater,
+      (None, None) => a.callsign.cmp(&b.callsign),
+    });
+    outbound.sort_by(|a, b| a.callsign.cmp(&b.callsign));
+
+    let movements = self.movements.read().await;
+    Some(AirportBoard {
+      airport: arpt.icao.clone(),
+      departures: movements.get(&arpt.icao, MovementKind::Takeoff),
+      arrivals: movements.get(&arpt.icao, MovementKind::Landing),
+      inbound,
+      outbound,
+    })
   }
 
   pub async fn get_pilot_by_callsign(&self, callsign: &str) -> Option<Pilot> {
@@ -469,11 +1014,47 @@ impl Manager {
   pub async fn get_pilot_track(
     &self,
     pilot: &Pilot,
-  ) -> Result<Option<Vec<TrackPoint>>, mongodb::error::Error> {
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
+    self.get_track_points(&pilot.track_code(), since).await
+  }
+
+  pub async fn get_track_points(
+    &self,
+    code: &str,
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
     if let Some(tracks) = &self.db {
-      tracks.read().await.get_track_points(pilot).await
+      tracks.get_track_points(code, since).await
     } else {
       Ok(None)
     }
   }
+
+  pub async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError> {
+    if let Some(tracks) = &self.db {
+      tracks.get_track_info(code).await
+    } else {
+      Ok(None)
+    }
+  }
+
+  pub async fn find_controller_sessions(
+    &self,
+    query: &SessionQuery,
+  ) -> Result<Vec<ControllerSession>, StoreError> {
+    if let Some(tracks) = &self.db {
+      tracks.find_controller_sessions(query).await
+    } else {
+      Ok(vec![])
+    }
+  }
+
+  pub async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError> {
+    if let Some(tracks) = &self.db {
+      tracks.find_tracks(query).await
+    } else {
+      Ok(vec![])
+    }
+  }
 }
@@ -0,0 +1,260 @@
+use crate::{
+  fixed::types::Airport,
+  moving::pilot::{FlightPhase, Pilot},
+  types::Point,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+// max difference between the pilot's heading and the runway heading
+const RUNWAY_HDG_TOLERANCE: i16 = 20;
+// max distance from the runway centerline
+const RUNWAY_MAX_OFFSET_NM: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementKind {
+  Takeoff,
+  Landing,
+}
+
+/// A takeoff or a landing detected at an airport
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Movement {
+  pub kind: MovementKind,
+  pub callsign: String,
+  pub cid: u32,
+  pub airport: String,
+  pub runway: Option<String>,
+  pub aircraft: Option<String>,
+  pub departure: Option<String>,
+  pub arrival: Option<String>,
+  pub time: DateTime<Utc>,
+}
+
+impl Movement {
+  pub fn new(kind: MovementKind, pilot: &Pilot, arpt: &Airport) -> Self {
+    let fp = pilot.flight_plan.as_ref();
+    Self {
+      kind,
+      callsign: pilot.callsign.clone(),
+      cid: pilot.cid,
+      airport: arpt.icao.clone(),
+      runway: match_runway(arpt, pilot.position, pilot.heading),
+      aircraft: fp.map(|fp| fp.aircraft.clone()),
+      departure: fp.map(|fp| fp.departure.clone()),
+      arrival: fp.map(|fp| fp.arrival.clone()),
+      time: pilot.last_updated,
+    }
+  }
+}
+
+fn hdg_delta(a: i16, b: i16) -> i16 {
+  ((b - a + 180).rem_euclid(360) - 180).abs()
+}
+
+/// Finds the runway the pilot is lined up with: the heading must match
+/// and the runway with the centerline closest to the pilot wins
+pub fn match_runway(arpt: &Airport, position: Point, heading: i16) -> Option<String> {
+  arpt
+    .runways
+    .values()
+    .filter(|rwy| !rwy.closed && (rwy.latitude != 0.0 || rwy.longitude != 0.0))
+    .filter(|rwy| hdg_delta(rwy.heading as i16, heading) <= RUNWAY_HDG_TOLERANCE)
+    .map(|rwy| {
+      // local flat projection is good enough within a few miles
+      let dx = (position.lng - rwy.longitude) * 60.0 * rwy.latitude.to_radians().cos();
+      let dy = (position.lat - rwy.latitude) * 60.0;
+      let hdg = (rwy.heading as f64).to_radians();
+      let offset = (dx * hdg.cos() - dy * hdg.sin()).abs();
+      (offset, rwy)
+    })
+    .filter(|(offset, _)| *offset <= RUNWAY_MAX_OFFSET_NM)
+    .min_by(|(a, _), (b, _)| a.total_cmp(b))
+    .map(|(_, rwy)| rwy.ident.clone())
+}
+
+/// MovementLog keeps recent takeoffs and landings by airport
+#[derive(Debug, Default)]
+pub struct MovementLog {
+  movements: HashMap<String, VecDeque<Movement>>,
+}
+
+impl MovementLog {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn push(&mut self, movement: Movement) {
+    self
+      .movements
+      .entry(movement.airport.clone())
+      .or_default()
+      .push_back(movement);
+  }
+
+  /// Drops movements older than max_age
+  pub fn expire(&mut self, max_age: Duration, now: DateTime<Utc>) {
+    let oldest = now - max_age;
+    for movements in self.movements.values_mut() {
+      while matches!(movements.front(), Some(m) if m.time < oldest) {
+        movements.pop_front();
+      }
+    }
+    self.movements.retain(|_, movements| !movements.is_empty());
+  }
+
+  /// Movements of the given kind at the airport, the most recent first
+  pub fn get(&self, icao: &str, kind: MovementKind) -> Vec<Movement> {
+    self
+      .movements
+      .get(icao)
+      .map(|movements| {
+        movements
+          .iter()
+          .rev()
+          .filter(|m| m.kind == kind)
+          .cloned()
+          .collect()
+      })
+      .unwrap_or_default()
+  }
+}
+
+/// A flight on an airport board: an outbound pilot still on the ground
+/// or an inbound one in the air
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardFlight {
+  pub callsign: String,
+  pub cid: u32,
+  pub aircraft: Option<String>,
+  pub departure: String,
+  pub arrival: String,
+  pub phase: Option<FlightPhase>,
+  pub eta: Option<DateTime<Utc>>,
+}
+
+impl BoardFlight {
+  pub fn new(pilot: &Pilot, eta: Option<DateTime<Utc>>) -> Option<Self> {
+    let fp = pilot.flight_plan.as_ref()?;
+    Some(Self {
+      callsign: pilot.callsign.clone(),
+      cid: pilot.cid,
+      aircraft: Some(fp.aircraft.clone()).filter(|aircraft| !aircraft.is_empty()),
+      departure: fp.departure.clone(),
+      arrival: fp.arrival.clone(),
+      phase: pilot.phase,
+      eta,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AirportBoard {
+  pub airport: String,
+  pub departures: Vec<Movement>,
+  pub arrivals: Vec<Movement>,
+  pub inbound: Vec<BoardFlight>,
+  pub outbound: Vec<BoardFlight>,
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    fixed::ourairports::Runway, moving::controller::ControllerSet, persistent::tests::test_pilot,
+  };
+
+  fn runway(ident: &str, lat: f64, lng: f64, heading: u16) -> Runway {
+    Runway {
+      icao: "EGLL".into(),
+      length_ft: 12000,
+      width_ft: 160,
+      surface: "ASP".into(),
+      lighted: true,
+      closed: false,
+      ident: ident.into(),
+      latitude: lat,
+      longitude: lng,
+      elevation_ft: 83,
+      heading,
+      active_to: false,
+      active_lnd: false,
+    }
+  }
+
+  pub fn test_airport() -> Airport {
+    let runways = [
+      runway("09L", 51.4775, -0.4849, 90),
+      runway("27R", 51.4777, -0.4333, 270),
+      runway("09R", 51.4647, -0.4822, 90),
+      runway("27L", 51.4650, -0.4340, 270),
+    ];
+    Airport {
+      icao: "EGLL".into(),
+      iata: "LHR".into(),
+      name: "Heathrow".into(),
+      position: Point {
+        lat: 51.4706,
+        lng: -0.4619,
+      },
+      fir_id: "EGTT".into(),
+      is_pseudo: false,
+      controllers: ControllerSet::empty(),
+      runways: runways
+        .into_iter()
+        .map(|rwy| (rwy.ident.clone(), rwy))
+        .collect(),
+      country: None,
+      wx: None,
+    }
+  }
+
+  #[test]
+  fn test_match_runway() {
+    let arpt = test_airport();
+    // departing 27L, just past the far end
+    let pos = Point {
+      lat: 51.4651,
+      lng: -0.4900,
+    };
+    assert_eq!(match_runway(&arpt, pos, 268).as_deref(), Some("27L"));
+    // landing 09L
+    let pos = Point {
+      lat: 51.4776,
+      lng: -0.4700,
+    };
+    assert_eq!(match_runway(&arpt, pos, 92).as_deref(), Some("09L"));
+    // crossing the runways
+    assert_eq!(match_runway(&arpt, pos, 0), None);
+  }
+
+  #[test]
+  fn test_movement_log() {
+    let arpt = test_airport();
+    let now = Utc::now();
+    let mut log = MovementLog::new();
+
+    let mut pilot = test_pilot("BAW1");
+    pilot.last_updated = now - Duration::hours(3);
+    log.push(Movement::new(MovementKind::Takeoff, &pilot, &arpt));
+    let mut pilot = test_pilot("BAW2");
+    pilot.last_updated = now - Duration::minutes(10);
+    log.push(Movement::new(MovementKind::Takeoff, &pilot, &arpt));
+    let mut pilot = test_pilot("BAW3");
+    pilot.last_updated = now - Duration::minutes(5);
+    log.push(Movement::new(MovementKind::Takeoff, &pilot, &arpt));
+    log.push(Movement::new(MovementKind::Landing, &pilot, &arpt));
+
+    let departures = log.get("EGLL", MovementKind::Takeoff);
+    assert_eq!(departures.len(), 3);
+    assert_eq!(departures[0].callsign, "BAW3");
+
+    log.expire(Duration::hours(2), now);
+    let departures = log.get("EGLL", MovementKind::Takeoff);
+    assert_eq!(departures.len(), 2);
+    assert_eq!(log.get("EGLL", MovementKind::Landing).len(), 1);
+    assert!(log.get("EGKK", MovementKind::Landing).is_empty());
+  }
+}
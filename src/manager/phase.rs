@@ -0,0 +1,367 @@
+use super::movements::{Movement, MovementKind};
+use crate::{
+  fixed::types::Airport,
+  moving::pilot::{FlightPhase, Pilot},
+};
+use chrono::{DateTime, Utc};
+use geo::HaversineDistance;
+use geo_types::Point as GeoPoint;
+use std::collections::HashMap;
+
+const METERS_PER_NM: f64 = 1852.0;
+// departure/arrival airports further than that don't count for AGL
+const FIELD_RADIUS_NM: f64 = 20.0;
+// below that height above the field an aircraft is considered on the ground
+const GROUND_AGL_FT: i32 = 100;
+// ground speed separating taxiing from takeoff and landing rolls
+const ROLL_GS: i32 = 40;
+const STOPPED_GS: i32 = 5;
+// vertical rate in feet per minute considered as level flight
+const LEVEL_FPM: i32 = 300;
+const TAKEOFF_AGL_FT: i32 = 1500;
+const APPROACH_AGL_FT: i32 = 3000;
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+  logon_time: DateTime<Utc>,
+  last_updated: DateTime<Utc>,
+  altitude: i32,
+  on_ground: bool,
+  phase: FlightPhase,
+}
+
+/// PhaseTracker follows pilots between iterations and derives their
+/// flight phase from successive snapshots. The vertical rate is computed
+/// from the altitude change since the previous snapshot and the height
+/// above the departure or arrival airport tells ground from air.
+/// Going from the ground to the air near an airport and back is collected
+/// as a takeoff or a landing.
+#[derive(Debug, Default)]
+pub struct PhaseTracker {
+  // previous snapshots by callsign
+  pilots: HashMap<String, Snapshot>,
+  movements: Vec<Movement>,
+}
+
+/// The flight plan departure or arrival airport, whichever is closer
+/// to the pilot, if the pilot is anywhere near it, with its elevation
+pub fn nearby_field<'a, F>(pilot: &Pilot, lookup: F) -> Option<(&'a Airport, i32)>
+where
+  F: Fn(&str) -> Option<(&'a Airport, i32)>,
+{
+  let fp = pilot.flight_plan.as_ref()?;
+  let position: GeoPoint = pilot.position.into();
+  [fp.departure.as_str(), fp.arrival.as_str()]
+    .into_iter()
+    .filter(|code| !code.is_empty())
+    .filter_map(lookup)
+    .map(|(arpt, elevation)| {
+      let dist = position.haversine_distance(&arpt.position.into()) / METERS_PER_NM;
+      (dist, arpt, elevation)
+    })
+    .filter(|(dist, _, _)| *dist <= FIELD_RADIUS_NM)
+    .min_by(|(a, _, _), (b, _, _)| a.total_cmp(b))
+    .map(|(_, arpt, elevation)| (arpt, elevation))
+}
+
+fn is_on_ground(altitude: i32, groundspeed: i32, elevation: Option<i32>) -> bool {
+  match elevation {
+    Some(elevation) => altitude - elevation < GROUND_AGL_FT,
+    // without the field elevation only the speed is left to go by
+    None => groundspeed < ROLL_GS,
+  }
+}
+
+fn derive(
+  prev: Option<FlightPhase>,
+  altitude: i32,
+  groundspeed: i32,
+  vertical_rate: i32,
+  elevation: Option<i32>,
+) -> FlightPhase {
+  let agl = elevation.map(|elevation| altitude - elevation);
+  let on_ground = is_on_ground(altitude, groundspeed, elevation);
+  let was_airborne = prev.map(|phase| phase.is_airborne()).unwrap_or(false);
+  let landed =
+    prev == Some(FlightPhase::Landed) || (was_airborne && prev != Some(FlightPhase::Takeoff));
+
+  if on_ground {
+    if groundspeed <= STOPPED_GS {
+      FlightPhase::Gate
+    } else if landed {
+      // the landing roll and taxiing in
+      FlightPhase::Landed
+    } else if groundspeed >= ROLL_GS {
+      FlightPhase::Takeoff
+    } else {
+      FlightPhase::Taxi
+    }
+  } else {
+    let low = |limit: i32| agl.map(|agl| agl < limit).unwrap_or(false);
+    let departing = matches!(
+      prev,
+      Some(FlightPhase::Gate) | Some(FlightPhase::Taxi) | Some(FlightPhase::Takeoff)
+    );
+    if departing && low(TAKEOFF_AGL_FT) {
+      FlightPhase::Takeoff
+    } else if vertical_rate > LEVEL_FPM {
+      FlightPhase::Climb
+    } else if vertical_rate < -LEVEL_FPM {
+      if low(APPROACH_AGL_FT) {
+        FlightPhase::Approach
+      } else {
+        FlightPhase::Descent
+      }
+    } else if low(APPROACH_AGL_FT)
+      && matches!(
+        prev,
+        Some(FlightPhase::Approach) | Some(FlightPhase::Descent)
+      )
+    {
+      FlightPhase::Approach
+    } else {
+      FlightPhase::Cruise
+    }
+  }
+}
+
+impl PhaseTracker {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// Returns the pilot's flight phase given the airport the pilot is at
+  /// or near and its elevation, if known
+  pub fn update(&mut self, pilot: &Pilot, field: Option<(&Airport, i32)>) -> FlightPhase {
+    let elevation = field.map(|(_, elevation)| elevation);
+    // a reconnected pilot starts over, so does one whose reports went
+    // back in time, i.e. when replay seeks backwards
+    let prev = self.pilots.get(&pilot.callsign).filter(|prev| {
+      prev.logon_time == pilot.logon_time && prev.last_updated <= pilot.last_updated
+    });
+
+    let vertical_rate = match prev {
+      Some(prev) => {
+        let dt = (pilot.last_updated - prev.last_updated).num_seconds();
+        if dt == 0 {
+          // the pilot hasn't reported since the previous iteration
+          return prev.phase;
+        }
+        ((pilot.altitude - prev.altitude) as i64 * 60 / dt) as i32
+      }
+      None => 0,
+    };
+
+    let on_ground = is_on_ground(pilot.altitude, pilot.groundspeed, elevation);
+    if let (Some(prev), Some((arpt, _))) = (prev, field) {
+      if prev.on_ground != on_ground {
+        let kind = if on_ground {
+          MovementKind::Landing
+        } else {
+          MovementKind::Takeoff
+        };
+        self.movements.push(Movement::new(kind, pilot, arpt));
+      }
+    }
+
+    let phase = derive(
+      prev.map(|prev| prev.phase),
+      pilot.altitude,
+      pilot.groundspeed,
+      vertical_rate,
+      elevation,
+    );
+    self.pilots.insert(
+      pilot.callsign.clone(),
+      Snapshot {
+        logon_time: pilot.logon_time,
+        last_updated: pilot.last_updated,
+        altitude: pilot.altitude,
+        on_ground,
+        phase,
+      },
+    );
+    phase
+  }
+
+  pub fn take_movements(&mut self) -> Vec<Movement> {
+    std::mem::take(&mut self.movements)
+  }
+
+  /// Forgets pilots which are not online anymore
+  pub fn forget_missing<F>(&mut self, is_online: F)
+  where
+    F: Fn(&str) -> bool,
+  {
+    self.pilots.retain(|cs, _| is_online(cs));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    manager::movements::tests::test_airport, moving::pilot::FlightPlan,
+    persistent::tests::test_pilot, types::Point,
+  };
+  use chrono::Duration;
+
+  const ELEVATION: i32 = 83;
+
+  fn step(tracker: &mut PhaseTracker, pilot: &mut Pilot, altitude: i32, gs: i32) -> FlightPhase {
+    let arpt = test_airport();
+    pilot.last_updated += Duration::seconds(60);
+    pilot.altitude = altitude;
+    pilot.groundspeed = gs;
+    tracker.update(pilot, Some((&arpt, ELEVATION)))
+  }
+
+  #[test]
+  fn test_flight_phases() {
+    let mut tracker = PhaseTracker::new();
+    let mut pilot = test_pilot("BAW123");
+
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 0), FlightPhase::Gate);
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 15), FlightPhase::Taxi);
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 83, 120),
+      FlightPhase::Takeoff
+    );
+    assert!(tracker.take_movements().is_empty());
+
+    // lifting off 27L
+    pilot.position = Point {
+      lat: 51.4651,
+      lng: -0.4900,
+    };
+    pilot.heading = 268;
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 900, 160),
+      FlightPhase::Takeoff
+    );
+    let movements = tracker.take_movements();
+    assert_eq!(movements.len(), 1);
+    assert_eq!(movements[0].kind, MovementKind::Takeoff);
+    assert_eq!(movements[0].airport, "EGLL");
+    assert_eq!(movements[0].runway.as_deref(), Some("27L"));
+
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 3500, 220),
+      FlightPhase::Climb
+    );
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 35000, 450),
+      FlightPhase::Climb
+    );
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 35000, 450),
+      FlightPhase::Cruise
+    );
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 33000, 440),
+      FlightPhase::Descent
+    );
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 3000, 250),
+      FlightPhase::Approach
+    );
+    // level segment of the approach
+    assert_eq!(
+      step(&mut tracker, &mut pilot, 3000, 180),
+      FlightPhase::Approach
+    );
+
+    // touching down on 09L
+    pilot.position = Point {
+      lat: 51.4776,
+      lng: -0.4700,
+    };
+    pilot.heading = 92;
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 130), FlightPhase::Landed);
+    let movements = tracker.take_movements();
+    assert_eq!(movements.len(), 1);
+    assert_eq!(movements[0].kind, MovementKind::Landing);
+    assert_eq!(movements[0].runway.as_deref(), Some("09L"));
+
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 20), FlightPhase::Landed);
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 0), FlightPhase::Gate);
+
+    // no new data, the phase stays
+    let arpt = test_airport();
+    assert_eq!(
+      tracker.update(&pilot, Some((&arpt, ELEVATION))),
+      FlightPhase::Gate
+    );
+
+    // going back in time, i.e. replay seeking backwards, starts over
+    pilot.last_updated -= Duration::seconds(3600);
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 20), FlightPhase::Taxi);
+
+    // forgotten pilots start over
+    tracker.forget_missing(|_| false);
+    assert_eq!(step(&mut tracker, &mut pilot, 83, 20), FlightPhase::Taxi);
+    assert!(tracker.take_movements().is_empty());
+  }
+
+  #[test]
+  fn test_unknown_elevation() {
+    let mut tracker = PhaseTracker::new();
+    let mut pilot = test_pilot("BAW123");
+    pilot.altitude = 5000;
+    pilot.groundspeed = 10;
+    // slow means on the ground without the field elevation
+    assert_eq!(tracker.update(&pilot, None), FlightPhase::Taxi);
+    pilot.last_updated += Duration::seconds(60);
+    pilot.groundspeed = 250;
+    assert_eq!(tracker.update(&pilot, None), FlightPhase::Cruise);
+    // no movements away from airports
+    assert!(tracker.take_movements().is_empty());
+  }
+
+  #[test]
+  fn test_nearby_field() {
+    let egll = test_airport();
+    let mut lfpg = test_airport();
+    lfpg.icao = "LFPG".into();
+    lfpg.position = Point {
+      lat: 49.01,
+      lng: 2.55,
+    };
+    let lookup = |code: &str| match code {
+      "EGLL" => Some((&egll, 83)),
+      "LFPG" => Some((&lfpg, 392)),
+      _ => None,
+    };
+    let elevation = |pilot: &Pilot| nearby_field(pilot, lookup).map(|(_, elevation)| elevation);
+
+    let mut pilot = test_pilot("BAW123");
+    assert_eq!(elevation(&pilot), None);
+
+    pilot.flight_plan = Some(FlightPlan {
+      flight_rules: "I".into(),
+      aircraft: "A320".into(),
+      departure: "EGLL".into(),
+      arrival: "LFPG".into(),
+      alternate: "".into(),
+      cruise_tas: 450,
+      altitude: 35000,
+      deptime: "".into(),
+      enroute_time: "".into(),
+      fuel_time: "".into(),
+      remarks: "".into(),
+      route: "".into(),
+    });
+    assert_eq!(elevation(&pilot), Some(83));
+    pilot.position = Point {
+      lat: 49.0,
+      lng: 2.5,
+    };
+    assert_eq!(elevation(&pilot), Some(392));
+    // en route
+    pilot.position = Point {
+      lat: 50.5,
+      lng: 1.0,
+    };
+    assert_eq!(elevation(&pilot), None);
+  }
+}
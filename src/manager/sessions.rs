@@ -0,0 +1,149 @@
+use crate::persistent::ControllerSession;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// SessionTracker follows online controllers between iterations and
+/// collects the sessions which have to be (re)stored: new ones, the ones
+/// which changed frequency or ATIS code, and the ones which have just ended.
+#[derive(Debug, Default)]
+pub struct SessionTracker {
+  // current sessions by callsign
+  sessions: HashMap<String, ControllerSession>,
+  changed: Vec<ControllerSession>,
+}
+
+impl SessionTracker {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn seen(&mut self, session: ControllerSession) {
+    let existing = self.sessions.get_mut(&session.callsign);
+    match existing {
+      Some(existing) if existing.code == session.code => {
+        let mut updated = existing.clone();
+        updated.merge_atis_codes(&session.atis_codes);
+        updated.freq = session.freq;
+        updated.facility = session.facility;
+        updated.airport = session.airport;
+        updated.fir = session.fir;
+        if &updated != existing {
+          *existing = updated.clone();
+          self.changed.push(updated);
+        }
+      }
+      _ => {
+        // the same callsign with a different code means the controller
+        // has reconnected since the previous iteration
+        if let Some(mut prev) = self.sessions.remove(&session.callsign) {
+          prev.logoff_time = Some(session.logon_time);
+          self.changed.push(prev);
+        }
+        self.changed.push(session.clone());
+        self.sessions.insert(session.callsign.clone(), session);
+      }
+    }
+  }
+
+  /// Ends the sessions of controllers which are not online anymore
+  pub fn close_missing<F>(&mut self, is_online: F, now: DateTime<Utc>)
+  where
+    F: Fn(&str) -> bool,
+  {
+    let gone: Vec<String> = self
+      .sessions
+      .keys()
+      .filter(|cs| !is_online(cs))
+      .cloned()
+      .collect();
+    for cs in gone {
+      let mut session = self.sessions.remove(&cs).unwrap();
+      session.logoff_time = Some(now);
+      self.changed.push(session);
+    }
+  }
+
+  pub fn take_changed(&mut self) -> Vec<ControllerSession> {
+    std::mem::take(&mut self.changed)
+  }
+
+  /// Returns the sessions which couldn't be stored so that they're taken
+  /// again next time. Sessions are stored as a whole so only the latest
+  /// version of each is kept.
+  pub fn put_back(&mut self, sessions: Vec<ControllerSession>) {
+    let mut codes = HashSet::new();
+    let mut changed: Vec<ControllerSession> = sessions
+      .into_iter()
+      .chain(self.changed.drain(..))
+      .rev()
+      .filter(|session| codes.insert(session.code.clone()))
+      .collect();
+    changed.reverse();
+    self.changed = changed;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{moving::controller::Facility, persistent::tests::test_controller};
+  use chrono::Duration;
+
+  #[test]
+  fn test_session_tracker() {
+    let mut tracker = SessionTracker::new();
+    let mut ctrl = test_controller("EGLL_ATIS", Facility::ATIS);
+    let session = ControllerSession::new(&ctrl, Some("EGLL".into()), None);
+    tracker.seen(session.clone());
+    assert_eq!(tracker.take_changed(), vec![session.clone()]);
+
+    // nothing has changed
+    tracker.seen(session.clone());
+    assert!(tracker.take_changed().is_empty());
+
+    ctrl.atis_code = "B".into();
+    tracker.seen(ControllerSession::new(&ctrl, Some("EGLL".into()), None));
+    let changed = tracker.take_changed();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].atis_codes, vec!["A", "B"]);
+
+    // reconnected
+    ctrl.logon_time += Duration::minutes(5);
+    let reconnected = ControllerSession::new(&ctrl, Some("EGLL".into()), None);
+    tracker.seen(reconnected.clone());
+    let changed = tracker.take_changed();
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[0].code, session.code);
+    assert_eq!(changed[0].logoff_time, Some(reconnected.logon_time));
+    assert_eq!(changed[1], reconnected);
+
+    let now = Utc::now();
+    tracker.close_missing(|cs| cs == "EGLL_ATIS", now);
+    assert!(tracker.take_changed().is_empty());
+    tracker.close_missing(|_| false, now);
+    let changed = tracker.take_changed();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].logoff_time, Some(now));
+  }
+
+  #[test]
+  fn test_put_back() {
+    let mut tracker = SessionTracker::new();
+    let ctrl = test_controller("EGLL_TWR", Facility::Tower);
+    tracker.seen(ControllerSession::new(&ctrl, Some("EGLL".into()), None));
+    let ctrl = test_controller("EGLL_GND", Facility::Ground);
+    let gnd = ControllerSession::new(&ctrl, Some("EGLL".into()), None);
+    tracker.seen(gnd.clone());
+    let failed = tracker.take_changed();
+
+    // the tower has gone offline meanwhile
+    let now = Utc::now();
+    tracker.close_missing(|cs| cs == "EGLL_GND", now);
+    tracker.put_back(failed);
+    let changed = tracker.take_changed();
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[0], gnd);
+    assert_eq!(changed[1].callsign, "EGLL_TWR");
+    assert_eq!(changed[1].logoff_time, Some(now));
+  }
+}
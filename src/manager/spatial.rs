@@ -1,5 +1,5 @@
 use crate::{
-  fixed::types::{Airport, FIR},
+  fixed::types::{Airport, FIR, TRACON, UIR},
   moving::pilot::Pilot,
   types::{Point, Rect},
 };
@@ -57,15 +57,40 @@ impl RTreeObject for RectObject {
   }
 }
 
-impl From<&FIR> for RectObject {
-  fn from(fir: &FIR) -> Self {
-    Self {
-      id: fir.icao.clone(),
-      rect: Rect {
-        south_west: fir.boundaries.min,
-        north_east: fir.boundaries.max,
-      },
-    }
+impl RectObject {
+  // bounding boxes crossing the antimeridian have min.lng > max.lng so
+  // they're split into two objects sharing the same id the way viewports
+  // are split in Rect::envelopes()
+  fn split(id: &str, min: Point, max: Point) -> Vec<Self> {
+    let rect = Rect {
+      south_west: min,
+      north_east: max,
+    };
+    rect
+      .envelopes()
+      .into_iter()
+      .map(|env| Self {
+        id: id.to_owned(),
+        rect: Rect {
+          south_west: env.lower(),
+          north_east: env.upper(),
+        },
+      })
+      .collect()
+  }
+
+  /// Builds index objects for a FIR, FIRs crossing the antimeridian
+  /// produce two of them
+  pub fn from_fir(fir: &FIR) -> Vec<Self> {
+    Self::split(&fir.icao, fir.boundaries.min, fir.boundaries.max)
+  }
+
+  pub fn from_tracon(tracon: &TRACON) -> Vec<Self> {
+    Self::split(&tracon.key(), tracon.min, tracon.max)
+  }
+
+  pub fn from_uir(uir: &UIR) -> Vec<Self> {
+    Self::split(&uir.icao, uir.min, uir.max)
   }
 }
 
@@ -78,7 +103,41 @@ impl PartialEq for RectObject {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::fixed::types::Boundaries;
   use rstar::RTree;
+  use std::collections::HashMap;
+
+  fn fir(icao: &str, min: Point, max: Point) -> FIR {
+    FIR {
+      icao: icao.into(),
+      name: icao.into(),
+      prefix: icao.into(),
+      boundaries: Boundaries {
+        id: icao.into(),
+        region: "APAC".into(),
+        division: "PAC".into(),
+        is_oceanic: true,
+        min,
+        max,
+        center: min,
+        points: vec![],
+      },
+      controllers: HashMap::new(),
+      country: None,
+    }
+  }
+
+  fn found(tree: &RTree<RectObject>, rect: Rect) -> Vec<String> {
+    let mut ids: Vec<String> = rect
+      .envelopes()
+      .iter()
+      .flat_map(|env| tree.locate_in_envelope_intersecting(env))
+      .map(|obj| obj.id.clone())
+      .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+  }
 
   #[test]
   fn test_intersection() {
@@ -101,4 +160,81 @@ mod tests {
     let objs = tree.locate_in_envelope(&env).collect::<Vec<_>>();
     assert_eq!(objs.len(), 0);
   }
+
+  #[test]
+  fn test_antimeridian() {
+    // VATSpy boundaries of oceanic Pacific FIRs
+    let nzzo = fir(
+      "NZZO",
+      Point {
+        lat: -52.5,
+        lng: 157.0,
+      },
+      Point {
+        lat: -5.0,
+        lng: -131.0,
+      },
+    );
+    let kzak = fir(
+      "KZAK",
+      Point {
+        lat: -3.5,
+        lng: 130.0,
+      },
+      Point {
+        lat: 43.0,
+        lng: -125.0,
+      },
+    );
+    let egtt = fir(
+      "EGTT",
+      Point {
+        lat: 49.8,
+        lng: -6.0,
+      },
+      Point {
+        lat: 55.8,
+        lng: 2.0,
+      },
+    );
+
+    let objs = RectObject::from_fir(&nzzo);
+    assert_eq!(objs.len(), 2);
+    assert!(objs.iter().all(|obj| obj.id == "NZZO"));
+    assert_eq!(RectObject::from_fir(&egtt).len(), 1);
+
+    let tree = RTree::bulk_load(
+      [&nzzo, &kzak, &egtt]
+        .into_iter()
+        .flat_map(RectObject::from_fir)
+        .collect(),
+    );
+
+    // Auckland, east of the antimeridian
+    assert_eq!(
+      found(&tree, Rect::new(174.0, -38.0, 176.0, -36.0)),
+      vec!["NZZO".to_owned()]
+    );
+    // French Polynesia, west of it
+    assert_eq!(
+      found(&tree, Rect::new(-150.0, -18.0, -148.0, -16.0)),
+      vec!["NZZO".to_owned()]
+    );
+    // Hawaii is covered by Oakland oceanic only
+    assert_eq!(
+      found(&tree, Rect::new(-158.0, 20.0, -156.0, 22.0)),
+      vec!["KZAK".to_owned()]
+    );
+    // viewport crossing the antimeridian
+    assert_eq!(
+      found(&tree, Rect::new(179.0, -20.0, -179.0, -10.0)),
+      vec!["NZZO".to_owned()]
+    );
+    // London and the Atlantic are far from both
+    assert_eq!(
+      found(&tree, Rect::new(-1.0, 51.0, 1.0, 52.0)),
+      vec!["EGTT".to_owned()]
+    );
+    assert!(found(&tree, Rect::new(-40.0, -30.0, -30.0, -20.0)).is_empty());
+  }
 }
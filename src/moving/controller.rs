@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
 use chrono::{DateTime, Utc};
 use serde::Serialize;
@@ -78,32 +78,57 @@ impl PartialEq for Controller {
   }
 }
 
+/// Airport controllers by facility, each facility may have several
+/// controllers online at once (i.e. EDDF_N_TWR and EDDF_S_TWR), keyed by callsign
 #[derive(Debug, Clone, Serialize, Default, PartialEq)]
 pub struct ControllerSet {
-  pub atis: Option<Controller>,
-  pub delivery: Option<Controller>,
-  pub ground: Option<Controller>,
-  pub tower: Option<Controller>,
-  pub approach: Option<Controller>,
+  pub atis: HashMap<String, Controller>,
+  pub delivery: HashMap<String, Controller>,
+  pub ground: HashMap<String, Controller>,
+  pub tower: HashMap<String, Controller>,
+  pub approach: HashMap<String, Controller>,
 }
 
 impl ControllerSet {
   pub fn empty() -> Self {
-    Self {
-      atis: None,
-      delivery: None,
-      ground: None,
-      tower: None,
-      approach: None,
-    }
+    Self::default()
   }
 
   pub fn is_empty(&self) -> bool {
-    self.atis.is_none()
-      && self.delivery.is_none()
-      && self.ground.is_none()
-      && self.tower.is_none()
-      && self.approach.is_none()
+    self.atis.is_empty()
+      && self.delivery.is_empty()
+      && self.ground.is_empty()
+      && self.tower.is_empty()
+      && self.approach.is_empty()
+  }
+
+  fn facility_mut(&mut self, facility: &Facility) -> Option<&mut HashMap<String, Controller>> {
+    match facility {
+      Facility::ATIS => Some(&mut self.atis),
+      Facility::Delivery => Some(&mut self.delivery),
+      Facility::Ground => Some(&mut self.ground),
+      Facility::Tower => Some(&mut self.tower),
+      Facility::Approach => Some(&mut self.approach),
+      _ => None,
+    }
+  }
+
+  /// Adds or replaces a controller, returns false if the facility
+  /// doesn't belong to an airport
+  pub fn insert(&mut self, ctrl: Controller) -> bool {
+    match self.facility_mut(&ctrl.facility) {
+      Some(ctrls) => {
+        ctrls.insert(ctrl.callsign.clone(), ctrl);
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn remove(&mut self, ctrl: &Controller) -> Option<Controller> {
+    self
+      .facility_mut(&ctrl.facility)
+      .and_then(|ctrls| ctrls.remove(&ctrl.callsign))
   }
 }
 
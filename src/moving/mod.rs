@@ -3,27 +3,21 @@ pub mod controller;
 pub mod data;
 mod exttypes;
 pub mod pilot;
+pub mod recorder;
+pub mod replay;
+pub mod source;
 
-use crate::config::Config;
 use data::Data;
-use log::error;
+use source::{FeedError, FeedSource};
 
-pub async fn load_vatsim_data(cfg: &Config) -> Option<Data> {
-  let res = reqwest::get(&cfg.api.url).await;
-  let response = match res {
-    Ok(response) => response,
-    Err(err) => {
-      error!("error loading vatsim data: {err:?}");
-      return None;
-    }
-  };
-  let res = response.json::<exttypes::Data>().await;
-  let data = match res {
-    Ok(data) => data,
-    Err(err) => {
-      error!("error parsing vatsim data: {err:?}");
-      return None;
-    }
-  };
-  Some(data.into())
+pub fn parse_vatsim_data(raw: &[u8]) -> Result<Data, FeedError> {
+  let data: exttypes::Data = serde_json::from_slice(raw)?;
+  Ok(data.into())
+}
+
+// raw data is returned along with the parsed one so it can be recorded
+pub async fn load_vatsim_data(source: &dyn FeedSource) -> Result<(Data, Vec<u8>), FeedError> {
+  let raw = source.fetch().await?;
+  let data = parse_vatsim_data(&raw)?;
+  Ok((data, raw))
 }
@@ -1,5 +1,8 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use geo::HaversineDistance;
+use geo_types::Point as GeoPoint;
 use serde::Serialize;
+use std::fmt::Display;
 
 use crate::types::Point;
 
@@ -23,6 +26,106 @@ pub struct Pilot {
   pub logon_time: DateTime<Utc>,
   pub last_updated: DateTime<Utc>,
   pub aircraft_type: Option<Vec<&'static Aircraft>>,
+  // FIR the pilot is in and the station controlling it, set by the manager
+  pub fir: Option<String>,
+  pub controlled_by: Option<String>,
+  // flight phase derived from successive snapshots, set by the manager
+  pub phase: Option<FlightPhase>,
+  // great-circle progress between the flight plan airports, set by the manager
+  pub progress: Option<FlightProgress>,
+}
+
+const METERS_PER_NM: f64 = 1852.0;
+// ETAs are not estimated for slower pilots
+const ETA_MIN_GS: i32 = 50;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FlightProgress {
+  pub dist_flown_nm: f64,
+  pub dist_remaining_nm: f64,
+  pub percent: f64,
+  pub eta: Option<DateTime<Utc>>,
+  pub eta_min: Option<i64>,
+}
+
+impl FlightProgress {
+  /// Progress of a pilot flying from dep to arr, the ETA assumes
+  /// the pilot goes straight to arr at the current groundspeed
+  pub fn new(pilot: &Pilot, dep: Point, arr: Point) -> Self {
+    let position: GeoPoint = pilot.position.into();
+    let dist_flown_nm = position.haversine_distance(&dep.into()) / METERS_PER_NM;
+    let dist_remaining_nm = position.haversine_distance(&arr.into()) / METERS_PER_NM;
+    let total = dist_flown_nm + dist_remaining_nm;
+    let percent = if total > 0.0 {
+      dist_flown_nm / total * 100.0
+    } else {
+      100.0
+    };
+    let eta_secs = if pilot.groundspeed >= ETA_MIN_GS {
+      Some((dist_remaining_nm / pilot.groundspeed as f64 * 3600.0).round() as i64)
+    } else {
+      None
+    };
+    Self {
+      dist_flown_nm,
+      dist_remaining_nm,
+      percent,
+      eta: eta_secs.map(|secs| pilot.last_updated + Duration::seconds(secs)),
+      eta_min: eta_secs.map(|secs| (secs + 30) / 60),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightPhase {
+  Gate,
+  Taxi,
+  Takeoff,
+  Climb,
+  Cruise,
+  Descent,
+  Approach,
+  Landed,
+}
+
+impl FlightPhase {
+  pub const ALL: [FlightPhase; 8] = [
+    FlightPhase::Gate,
+    FlightPhase::Taxi,
+    FlightPhase::Takeoff,
+    FlightPhase::Climb,
+    FlightPhase::Cruise,
+    FlightPhase::Descent,
+    FlightPhase::Approach,
+    FlightPhase::Landed,
+  ];
+
+  pub fn is_airborne(&self) -> bool {
+    matches!(
+      self,
+      FlightPhase::Takeoff
+        | FlightPhase::Climb
+        | FlightPhase::Cruise
+        | FlightPhase::Descent
+        | FlightPhase::Approach
+    )
+  }
+}
+
+impl Display for FlightPhase {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FlightPhase::Gate => write!(f, "gate"),
+      FlightPhase::Taxi => write!(f, "taxi"),
+      FlightPhase::Takeoff => write!(f, "takeoff"),
+      FlightPhase::Climb => write!(f, "climb"),
+      FlightPhase::Cruise => write!(f, "cruise"),
+      FlightPhase::Descent => write!(f, "descent"),
+      FlightPhase::Approach => write!(f, "approach"),
+      FlightPhase::Landed => write!(f, "landed"),
+    }
+  }
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
@@ -115,6 +218,46 @@ impl From<crate::moving::exttypes::Pilot> for Pilot {
       logon_time,
       last_updated,
       aircraft_type,
+      fir: None,
+      controlled_by: None,
+      phase: None,
+      progress: None,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistent::tests::test_pilot;
+
+  #[test]
+  fn test_flight_progress() {
+    let dep = Point {
+      lat: 51.47,
+      lng: -0.46,
+    };
+    let arr = Point {
+      lat: 51.47,
+      lng: 2.46,
+    };
+    let mut pilot = test_pilot("BAW1");
+    pilot.position = Point {
+      lat: 51.47,
+      lng: 1.0,
+    };
+    let progress = FlightProgress::new(&pilot, dep, arr);
+    assert!((progress.dist_flown_nm - 54.6).abs() < 0.5);
+    assert!((progress.dist_remaining_nm - 54.6).abs() < 0.5);
+    assert!((progress.percent - 50.0).abs() < 0.5);
+    // not moving
+    assert_eq!(progress.eta, None);
+    assert_eq!(progress.eta_min, None);
+
+    pilot.groundspeed = 300;
+    let progress = FlightProgress::new(&pilot, dep, arr);
+    assert_eq!(progress.eta_min, Some(11));
+    let mins = (progress.eta.unwrap() - pilot.last_updated).num_minutes();
+    assert_eq!(mins, 10);
+  }
+}
@@ -0,0 +1,149 @@
+/// Raw feed recorder
+/// Every new vatsim-data.json snapshot is gzipped as is and saved to the
+/// recorder directory, file names are made of the snapshot timestamps so they
+/// sort chronologically and can be fed back with the replay source.
+use crate::config::Recorder as RecorderConfig;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use log::{debug, info};
+use std::{
+  fs,
+  io::{self, Write},
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+const PREFIX: &str = "vatsim-data-";
+const SUFFIX: &str = ".json.gz";
+const TS_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug, Clone)]
+pub struct FeedRecorder {
+  dir: PathBuf,
+  max_age: Duration,
+  max_files: usize,
+}
+
+pub fn snapshot_name(updated_at: DateTime<Utc>) -> String {
+  format!("{PREFIX}{}{SUFFIX}", updated_at.format(TS_FORMAT))
+}
+
+// works for both gzipped snapshots and the ones unpacked manually
+pub fn snapshot_time(name: &str) -> Option<DateTime<Utc>> {
+  let ts = name.strip_prefix(PREFIX)?;
+  let ts = ts
+    .strip_suffix(SUFFIX)
+    .or_else(|| ts.strip_suffix(".json"))?;
+  let ts = NaiveDateTime::parse_from_str(ts, TS_FORMAT).ok()?;
+  Some(DateTime::from_utc(ts, Utc))
+}
+
+impl FeedRecorder {
+  pub fn new(cfg: &RecorderConfig) -> Self {
+    Self {
+      dir: cfg.dir.clone().into(),
+      max_age: cfg.max_age,
+      max_files: cfg.max_files,
+    }
+  }
+
+  /// Saves a raw snapshot and drops the ones exceeding retention limits.
+  /// Compression takes a while for a full-size feed so this runs on
+  /// the blocking thread pool.
+  pub async fn record(&self, raw: Vec<u8>, updated_at: DateTime<Utc>) -> io::Result<PathBuf> {
+    let rec = self.clone();
+    tokio::task::spawn_blocking(move || {
+      let path = rec.write(&raw, updated_at)?;
+      rec.prune(Utc::now())?;
+      Ok(path)
+    })
+    .await?
+  }
+
+  fn write(&self, raw: &[u8], updated_at: DateTime<Utc>) -> io::Result<PathBuf> {
+    fs::create_dir_all(&self.dir)?;
+    let path = self.dir.join(snapshot_name(updated_at));
+    // write-then-rename so the replay source never sees a partial file
+    let tmp_path = path.with_extension("tmp");
+    let f = fs::File::create(&tmp_path)?;
+    let mut enc = GzEncoder::new(f, Compression::default());
+    enc.write_all(raw)?;
+    enc.finish()?;
+    fs::rename(&tmp_path, &path)?;
+    debug!("vatsim data snapshot saved to {}", path.display());
+    Ok(path)
+  }
+
+  fn prune(&self, now: DateTime<Utc>) -> io::Result<()> {
+    let mut snapshots = list_snapshots(&self.dir)?;
+    let max_age = chrono::Duration::from_std(self.max_age).unwrap();
+    let threshold = now - max_age;
+
+    let excess = snapshots.len().saturating_sub(self.max_files);
+    let mut dropped = 0;
+    for (idx, (name, ts)) in snapshots.drain(..).enumerate() {
+      if idx < excess || ts < threshold {
+        fs::remove_file(self.dir.join(name))?;
+        dropped += 1;
+      }
+    }
+    if dropped > 0 {
+      info!("{dropped} outdated vatsim data snapshots dropped");
+    }
+    Ok(())
+  }
+}
+
+// recorded snapshots along with their timestamps, oldest first
+fn list_snapshots(dir: &Path) -> io::Result<Vec<(String, DateTime<Utc>)>> {
+  let mut snapshots = vec![];
+  for entry in fs::read_dir(dir)? {
+    let name = entry?.file_name().to_string_lossy().to_string();
+    if let Some(ts) = snapshot_time(&name) {
+      snapshots.push((name, ts));
+    }
+  }
+  snapshots.sort();
+  Ok(snapshots)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::moving::{
+    replay::ReplaySource,
+    source::{tests::test_dir, FeedSource},
+  };
+  use chrono::TimeZone;
+
+  #[tokio::test]
+  async fn test_recorder() {
+    let dir = test_dir();
+    let cfg = RecorderConfig {
+      enabled: true,
+      dir: dir.to_str().unwrap().to_owned(),
+      max_age: Duration::from_secs(86400 * 365 * 100),
+      max_files: 2,
+    };
+    let rec = FeedRecorder::new(&cfg);
+    let t = Utc.with_ymd_and_hms(2022, 12, 20, 10, 0, 0).unwrap();
+    for i in 0..3 {
+      let ts = t + chrono::Duration::seconds(i * 15);
+      let path = rec.record(format!("{i}").into_bytes(), ts).await.unwrap();
+      assert!(path.is_file());
+    }
+
+    let snapshots = list_snapshots(&dir).unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[0].0, "vatsim-data-20221220T100015Z.json.gz");
+
+    // recordings can be replayed
+    let source = ReplaySource::new(dir.to_str().unwrap(), 1.0).unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"1");
+    assert_eq!(source.fetch().await.unwrap(), b"2");
+
+    rec.prune(t + chrono::Duration::days(36501)).unwrap();
+    assert!(list_snapshots(&dir).unwrap().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
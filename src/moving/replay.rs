@@ -0,0 +1,326 @@
+/// Snapshot replay
+/// ReplaySource feeds snapshots stored in a directory one by one in the order
+/// of their file names, both plain and gzipped (.json.gz) ones. The directory
+/// is re-read on every fetch so snapshots added meanwhile are picked up as
+/// well. Once all the snapshots are replayed the last one is returned over
+/// and over again.
+///
+/// Snapshots named by the recorder carry their timestamps so these are
+/// replayed at their original pace multiplied by the replay speed, and
+/// it's possible to seek to a particular moment. Replay can be paused
+/// and advanced step by step as well.
+use super::{
+  recorder::snapshot_time,
+  source::{FeedError, FeedSource},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use log::info;
+use serde::Serialize;
+use std::{io::Read, path::PathBuf, time::Duration};
+use tokio::{
+  select,
+  sync::{Mutex, Notify},
+  time::sleep,
+};
+
+#[derive(Debug)]
+struct ReplayState {
+  // file name of the current snapshot
+  current: Option<String>,
+  // file name of the snapshot to jump to on the next fetch
+  seek: Option<String>,
+  paused: bool,
+  // steps requested while paused
+  steps: usize,
+  speed: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayStatus {
+  pub paused: bool,
+  pub speed: f64,
+  pub snapshot: Option<String>,
+  pub snapshot_time: Option<DateTime<Utc>>,
+  pub first_snapshot_time: Option<DateTime<Utc>>,
+  pub last_snapshot_time: Option<DateTime<Utc>>,
+  pub snapshots: usize,
+}
+
+#[derive(Debug)]
+pub struct ReplaySource {
+  dir: PathBuf,
+  state: Mutex<ReplayState>,
+  // wakes wait() up when the replay settings change
+  wake: Notify,
+}
+
+fn check_speed(speed: f64) -> Result<f64, FeedError> {
+  if !speed.is_finite() || speed <= 0.0 {
+    return Err(FeedError::Replay(format!("invalid replay speed {speed}")));
+  }
+  Ok(speed)
+}
+
+impl ReplaySource {
+  pub fn new(dir: &str, speed: f64) -> Result<Self, FeedError> {
+    Ok(Self {
+      dir: dir.into(),
+      state: Mutex::new(ReplayState {
+        current: None,
+        seek: None,
+        paused: false,
+        steps: 0,
+        speed: check_speed(speed)?,
+      }),
+      wake: Notify::new(),
+    })
+  }
+
+  async fn list(&self) -> Result<Vec<String>, FeedError> {
+    let mut names = vec![];
+    let mut entries = tokio::fs::read_dir(&self.dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      if !entry.file_type().await?.is_file() {
+        continue;
+      }
+      let name = entry.file_name().to_string_lossy().to_string();
+      if name.ends_with(".json") || name.ends_with(".json.gz") {
+        names.push(name);
+      }
+    }
+    names.sort();
+    Ok(names)
+  }
+
+  // real time to wait before moving on to the snapshot after the current one
+  fn delay(names: &[String], state: &ReplayState, poll_period: Duration) -> Duration {
+    let timing = state.current.as_ref().and_then(|current| {
+      let next = names.iter().find(|name| *name > current)?;
+      let elapsed = snapshot_time(next)? - snapshot_time(current)?;
+      elapsed.to_std().ok()
+    });
+    timing.unwrap_or(poll_period).div_f64(state.speed)
+  }
+
+  pub async fn pause(&self) {
+    self.state.lock().await.paused = true;
+    self.wake.notify_one();
+  }
+
+  pub async fn resume(&self) {
+    let mut state = self.state.lock().await;
+    state.paused = false;
+    state.steps = 0;
+    self.wake.notify_one();
+  }
+
+  /// Pauses replay if it's running and moves one snapshot forward
+  pub async fn step(&self) {
+    let mut state = self.state.lock().await;
+    state.paused = true;
+    state.steps += 1;
+    self.wake.notify_one();
+  }
+
+  pub async fn set_speed(&self, speed: f64) -> Result<(), FeedError> {
+    self.state.lock().await.speed = check_speed(speed)?;
+    self.wake.notify_one();
+    Ok(())
+  }
+
+  /// Jumps to the first snapshot taken at or after the given moment, returns
+  /// the snapshot name. Both directions work.
+  pub async fn seek(&self, to: DateTime<Utc>) -> Result<String, FeedError> {
+    let names = self.list().await?;
+    let name = names
+      .into_iter()
+      .find(|name| snapshot_time(name).map(|ts| ts >= to).unwrap_or(false))
+      .ok_or_else(|| FeedError::Replay(format!("no snapshots found at or after {to}")))?;
+    info!("seeking replay to {name}");
+    self.state.lock().await.seek = Some(name.clone());
+    self.wake.notify_one();
+    Ok(name)
+  }
+
+  pub async fn status(&self) -> Result<ReplayStatus, FeedError> {
+    let names = self.list().await?;
+    let times: Vec<DateTime<Utc>> = names
+      .iter()
+      .filter_map(|name| snapshot_time(name))
+      .collect();
+    let state = self.state.lock().await;
+    Ok(ReplayStatus {
+      paused: state.paused,
+      speed: state.speed,
+      snapshot: state.current.clone(),
+      snapshot_time: state.current.as_ref().and_then(|name| snapshot_time(name)),
+      first_snapshot_time: times.first().cloned(),
+      last_snapshot_time: times.last().cloned(),
+      snapshots: names.len(),
+    })
+  }
+}
+
+#[async_trait]
+impl FeedSource for ReplaySource {
+  async fn fetch(&self) -> Result<Vec<u8>, FeedError> {
+    let names = self.list().await?;
+    let mut state = self.state.lock().await;
+
+    let next = match (state.seek.take(), &state.current) {
+      (Some(seek), _) => Some(seek),
+      (None, Some(current)) => Some(
+        names
+          .iter()
+          .find(|name| *name > current)
+          .unwrap_or(current)
+          .clone(),
+      ),
+      (None, None) => names.first().cloned(),
+    };
+    let name = next.ok_or_else(|| FeedError::NoSnapshots(self.dir.display().to_string()))?;
+
+    if state.current.as_ref() != Some(&name) {
+      info!("replaying snapshot {name}");
+    }
+    let data = tokio::fs::read(self.dir.join(&name)).await?;
+    let data = if name.ends_with(".gz") {
+      // snapshots saved by the recorder
+      let mut raw = vec![];
+      GzDecoder::new(data.as_slice()).read_to_end(&mut raw)?;
+      raw
+    } else {
+      data
+    };
+    state.current = Some(name);
+    Ok(data)
+  }
+
+  async fn wait(&self, poll_period: Duration) {
+    loop {
+      let names = self.list().await.unwrap_or_default();
+      let delay = {
+        let mut state = self.state.lock().await;
+        if state.seek.is_some() {
+          return;
+        }
+        if state.paused {
+          if state.steps > 0 {
+            state.steps -= 1;
+            return;
+          }
+          None
+        } else {
+          Some(Self::delay(&names, &state, poll_period))
+        }
+      };
+
+      match delay {
+        None => self.wake.notified().await,
+        Some(delay) => {
+          select! {
+            _ = sleep(delay) => return,
+            // settings have changed, the delay has to be recalculated
+            _ = self.wake.notified() => {},
+          }
+        }
+      }
+    }
+  }
+
+  fn replay(&self) -> Option<&ReplaySource> {
+    Some(self)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::moving::{recorder::snapshot_name, source::tests::test_dir};
+  use chrono::TimeZone;
+  use std::fs;
+  use tokio::time::timeout;
+
+  #[tokio::test]
+  async fn test_replay_source() {
+    let dir = test_dir();
+    let source = ReplaySource::new(dir.to_str().unwrap(), 1.0).unwrap();
+    assert!(matches!(
+      source.fetch().await,
+      Err(FeedError::NoSnapshots(_))
+    ));
+
+    fs::write(dir.join("002.json"), "2").unwrap();
+    fs::write(dir.join("001.json"), "1").unwrap();
+    fs::write(dir.join("notes.txt"), "skipped").unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"1");
+    assert_eq!(source.fetch().await.unwrap(), b"2");
+    assert_eq!(source.fetch().await.unwrap(), b"2");
+
+    // new snapshots are picked up
+    fs::write(dir.join("003.json"), "3").unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"3");
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_replay_controls() {
+    let dir = test_dir();
+    let t = Utc.with_ymd_and_hms(2022, 12, 20, 10, 0, 0).unwrap();
+    for i in 0..4 {
+      let ts = t + chrono::Duration::seconds(i * 15);
+      fs::write(
+        dir.join(snapshot_name(ts).replace(".gz", "")),
+        format!("{i}"),
+      )
+      .unwrap();
+    }
+
+    // 15 seconds between snapshots take 15ms
+    assert!(ReplaySource::new(dir.to_str().unwrap(), 0.0).is_err());
+    assert!(ReplaySource::new(dir.to_str().unwrap(), -1.0).is_err());
+    let source = ReplaySource::new(dir.to_str().unwrap(), 1000.0).unwrap();
+    let poll_period = Duration::from_secs(15);
+    assert_eq!(source.fetch().await.unwrap(), b"0");
+    timeout(Duration::from_secs(1), source.wait(poll_period))
+      .await
+      .unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"1");
+
+    source.pause().await;
+    let res = timeout(Duration::from_millis(100), source.wait(poll_period)).await;
+    assert!(res.is_err());
+    source.step().await;
+    timeout(Duration::from_secs(1), source.wait(poll_period))
+      .await
+      .unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"2");
+
+    // seeking works while paused
+    let name = source
+      .seek(t + chrono::Duration::seconds(10))
+      .await
+      .unwrap();
+    assert_eq!(name, "vatsim-data-20221220T100015Z.json");
+    timeout(Duration::from_secs(1), source.wait(poll_period))
+      .await
+      .unwrap();
+    assert_eq!(source.fetch().await.unwrap(), b"1");
+    assert!(source.seek(t + chrono::Duration::days(1)).await.is_err());
+
+    assert!(source.set_speed(0.0).await.is_err());
+    let status = source.status().await.unwrap();
+    assert!(status.paused);
+    assert_eq!(status.snapshots, 4);
+    assert_eq!(
+      status.snapshot_time,
+      Some(t + chrono::Duration::seconds(15))
+    );
+    assert_eq!(status.first_snapshot_time, Some(t));
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
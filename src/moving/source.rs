@@ -0,0 +1,169 @@
+/// VATSIM feed sources
+/// The feed is normally polled over HTTP, however it can be read from a local
+/// file or replayed from a directory of recorded snapshots, which is handy
+/// for tests, demos and reproducing bugs.
+use super::replay::ReplaySource;
+use crate::config::{Api as ApiConfig, FeedSourceType};
+use async_trait::async_trait;
+use reqwest::Client;
+use std::{error::Error, fmt::Display, path::PathBuf, time::Duration};
+use tokio::time::sleep;
+
+#[derive(Debug)]
+pub enum FeedError {
+  Http(reqwest::Error),
+  Io(std::io::Error),
+  Parse(serde_json::Error),
+  NoSnapshots(String),
+  NotReplaying,
+  Replay(String),
+}
+
+impl Display for FeedError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FeedError::Http(err) => write!(f, "http error: {}", err),
+      FeedError::Io(err) => write!(f, "io error: {}", err),
+      FeedError::Parse(err) => write!(f, "parse error: {}", err),
+      FeedError::NoSnapshots(dir) => write!(f, "no snapshots found in {}", dir),
+      FeedError::NotReplaying => write!(f, "vatsim data is not being replayed"),
+      FeedError::Replay(msg) => write!(f, "replay error: {}", msg),
+    }
+  }
+}
+
+impl Error for FeedError {}
+
+impl From<reqwest::Error> for FeedError {
+  fn from(err: reqwest::Error) -> Self {
+    FeedError::Http(err)
+  }
+}
+
+impl From<std::io::Error> for FeedError {
+  fn from(err: std::io::Error) -> Self {
+    FeedError::Io(err)
+  }
+}
+
+impl From<serde_json::Error> for FeedError {
+  fn from(err: serde_json::Error) -> Self {
+    FeedError::Parse(err)
+  }
+}
+
+/// FeedSource returns raw vatsim-data.json contents, parsing is done
+/// separately so the raw data can be recorded as is
+#[async_trait]
+pub trait FeedSource: std::fmt::Debug + Send + Sync {
+  async fn fetch(&self) -> Result<Vec<u8>, FeedError>;
+
+  // waits until the next fetch is due, live sources are simply polled
+  // every poll period while replay pace depends on the replay settings
+  async fn wait(&self, poll_period: Duration) {
+    sleep(poll_period).await
+  }
+
+  fn replay(&self) -> Option<&ReplaySource> {
+    None
+  }
+}
+
+#[derive(Debug)]
+pub struct HttpSource {
+  url: String,
+  client: Client,
+}
+
+impl HttpSource {
+  pub fn new(url: &str, timeout: Duration) -> Self {
+    let client = Client::builder().timeout(timeout).build().unwrap();
+    Self {
+      url: url.to_owned(),
+      client,
+    }
+  }
+}
+
+#[async_trait]
+impl FeedSource for HttpSource {
+  async fn fetch(&self) -> Result<Vec<u8>, FeedError> {
+    let response = self.client.get(&self.url).send().await?;
+    let response = response.error_for_status()?;
+    let body = response.bytes().await?;
+    Ok(body.to_vec())
+  }
+}
+
+/// FileSource reads the same file on every fetch, the file is
+/// expected to be updated by something else
+#[derive(Debug)]
+pub struct FileSource {
+  path: PathBuf,
+}
+
+impl FileSource {
+  pub fn new(path: &str) -> Self {
+    Self { path: path.into() }
+  }
+}
+
+#[async_trait]
+impl FeedSource for FileSource {
+  async fn fetch(&self) -> Result<Vec<u8>, FeedError> {
+    let data = tokio::fs::read(&self.path).await?;
+    Ok(data)
+  }
+}
+
+pub fn create_source(cfg: &ApiConfig) -> Result<Box<dyn FeedSource>, FeedError> {
+  let source: Box<dyn FeedSource> = match cfg.source {
+    FeedSourceType::Http => Box::new(HttpSource::new(&cfg.url, cfg.timeout)),
+    FeedSourceType::File => Box::new(FileSource::new(&cfg.path)),
+    FeedSourceType::Replay => Box::new(ReplaySource::new(&cfg.path, cfg.replay_speed)?),
+  };
+  Ok(source)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use std::{env::temp_dir, fs};
+  use uuid::Uuid;
+
+  pub fn test_snapshot(updated_at: &str) -> String {
+    format!(
+      r#"{{
+        "general": {{
+          "version": 3,
+          "reload": 1,
+          "update": "",
+          "update_timestamp": "{updated_at}",
+          "connected_clients": 1,
+          "unique_users": 1
+        }},
+        "pilots": [],
+        "controllers": [],
+        "atis": []
+      }}"#
+    )
+  }
+
+  pub fn test_dir() -> PathBuf {
+    let dir = temp_dir().join(format!("camden-test-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[tokio::test]
+  async fn test_file_source() {
+    let dir = test_dir();
+    let path = dir.join("vatsim-data.json");
+    fs::write(&path, test_snapshot("2022-12-20T10:00:00Z")).unwrap();
+    let source = FileSource::new(path.to_str().unwrap());
+    let raw = source.fetch().await.unwrap();
+    let data = crate::moving::parse_vatsim_data(&raw).unwrap();
+    assert_eq!(data.general.updated_at.timestamp(), 1671530400);
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,198 @@
+/// Track change detection
+/// The vatsim feed reports every pilot on every poll, including aircraft
+/// parked at gates for hours. ChangeDetector compares each new point with
+/// the last stored point of the same track and lets it through only if the
+/// pilot has moved or changed altitude, heading or speed noticeably, or
+/// the flight details have changed, i.e. a flight plan has been filed.
+use super::{PendingPoint, TrackPoint};
+use crate::config::Track as TrackConfig;
+use geo::{HaversineDistance, Point};
+use std::collections::HashMap;
+
+const METERS_PER_NM: f64 = 1852.0;
+
+#[derive(Debug)]
+pub struct ChangeDetector {
+  min_distance_nm: f64,
+  min_alt_delta: i32,
+  min_hdg_delta: i16,
+  min_gs_delta: i32,
+  min_interval_ms: i64,
+  max_interval_ms: i64,
+  // last stored point by track code
+  last: HashMap<String, PendingPoint>,
+}
+
+fn distance_nm(a: &TrackPoint, b: &TrackPoint) -> f64 {
+  let a = Point::new(a.lng, a.lat);
+  let b = Point::new(b.lng, b.lat);
+  a.haversine_distance(&b) / METERS_PER_NM
+}
+
+fn hdg_delta(a: i16, b: i16) -> i16 {
+  ((b - a + 180).rem_euclid(360) - 180).abs()
+}
+
+impl ChangeDetector {
+  pub fn new(cfg: &TrackConfig) -> Self {
+    Self {
+      min_distance_nm: cfg.min_distance_nm,
+      min_alt_delta: cfg.min_alt_delta,
+      min_hdg_delta: cfg.min_hdg_delta,
+      min_gs_delta: cfg.min_gs_delta,
+      min_interval_ms: cfg.min_interval.as_millis() as i64,
+      max_interval_ms: cfg.max_interval.as_millis() as i64,
+      last: HashMap::new(),
+    }
+  }
+
+  fn changed(&self, last: &TrackPoint, tp: &TrackPoint) -> bool {
+    let elapsed = tp.ts - last.ts;
+    if elapsed < self.min_interval_ms {
+      return false;
+    }
+    if elapsed >= self.max_interval_ms {
+      return true;
+    }
+    distance_nm(last, tp) >= self.min_distance_nm
+      || (tp.alt - last.alt).abs() >= self.min_alt_delta
+      || hdg_delta(last.hdg, tp.hdg) >= self.min_hdg_delta
+      || (tp.gs - last.gs).abs() >= self.min_gs_delta
+  }
+
+  /// Returns true if the point should be stored, remembering it as the
+  /// last stored point of its track
+  pub fn check(&mut self, pp: &PendingPoint) -> bool {
+    if let Some(last) = self.last.get(&pp.info.code) {
+      if last.info == pp.info && !self.changed(&last.point, &pp.point) {
+        return false;
+      }
+    }
+    self.last.insert(pp.info.code.clone(), pp.clone());
+    true
+  }
+
+  /// Forgets the given tracks when their accepted points couldn't be
+  /// stored after all, so the next point of each is stored
+  pub fn forget<'a, I>(&mut self, codes: I)
+  where
+    I: IntoIterator<Item = &'a str>,
+  {
+    for code in codes {
+      self.last.remove(code);
+    }
+  }
+
+  /// Forgets tracks which haven't stored anything for a while. Active
+  /// tracks store a point at least every max_interval so these
+  /// belong to pilots who went offline.
+  pub fn purge(&mut self, now_ms: i64) {
+    let threshold = now_ms - 2 * self.max_interval_ms;
+    self.last.retain(|_, pp| pp.point.ts >= threshold);
+  }
+
+  pub fn len(&self) -> usize {
+    self.last.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.last.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistent::TrackInfo;
+  use chrono::{TimeZone, Utc};
+
+  fn pp(code: &str, lat: f64, alt: i32, hdg: i16, gs: i32, ts: i64) -> PendingPoint {
+    PendingPoint {
+      info: TrackInfo {
+        code: code.into(),
+        cid: 1000000,
+        callsign: code.into(),
+        logon_time: Utc.timestamp_opt(0, 0).unwrap(),
+        departure: None,
+        arrival: None,
+      },
+      point: TrackPoint {
+        lat,
+        lng: -0.45,
+        alt,
+        hdg,
+        gs,
+        ts,
+      },
+    }
+  }
+
+  #[test]
+  fn test_parked_pilot() {
+    let mut det = ChangeDetector::new(&TrackConfig::default());
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 0)));
+    // nothing changes for 4 minutes
+    for i in 1..16 {
+      assert!(!det.check(&pp("BAW1", 51.47, 83, 270, 0, i * 15000)));
+    }
+    // max_interval has passed since the last stored point
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 300000)));
+    // other tracks are independent
+    assert!(det.check(&pp("BAW2", 51.47, 83, 270, 0, 300000)));
+  }
+
+  #[test]
+  fn test_changes() {
+    let mut det = ChangeDetector::new(&TrackConfig::default());
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 0)));
+    // position, ~0.1nm north
+    assert!(det.check(&pp("BAW1", 51.4717, 83, 270, 0, 15000)));
+    // altitude
+    assert!(!det.check(&pp("BAW1", 51.4717, 150, 270, 0, 30000)));
+    assert!(det.check(&pp("BAW1", 51.4717, 200, 270, 0, 45000)));
+    // heading, across north
+    assert!(det.check(&pp("BAW1", 51.4717, 200, 358, 0, 60000)));
+    assert!(det.check(&pp("BAW1", 51.4717, 200, 3, 0, 75000)));
+    assert!(!det.check(&pp("BAW1", 51.4717, 200, 359, 0, 90000)));
+    // groundspeed
+    assert!(det.check(&pp("BAW1", 51.4717, 200, 3, 15, 105000)));
+    // flight plan filed
+    let mut p = pp("BAW1", 51.4717, 200, 3, 15, 120000);
+    assert!(!det.check(&p));
+    p.info.arrival = Some("EGLL".into());
+    assert!(det.check(&p));
+  }
+
+  #[test]
+  fn test_min_interval() {
+    let cfg = TrackConfig {
+      min_interval: std::time::Duration::from_secs(30),
+      ..Default::default()
+    };
+    let mut det = ChangeDetector::new(&cfg);
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 0)));
+    assert!(!det.check(&pp("BAW1", 51.47, 3000, 270, 250, 15000)));
+    assert!(det.check(&pp("BAW1", 51.47, 3000, 270, 250, 30000)));
+  }
+
+  #[test]
+  fn test_purge() {
+    let mut det = ChangeDetector::new(&TrackConfig::default());
+    det.check(&pp("BAW1", 51.47, 83, 270, 0, 0));
+    det.check(&pp("BAW2", 51.47, 83, 270, 0, 500000));
+    det.purge(700000);
+    assert_eq!(det.len(), 1);
+  }
+
+  #[test]
+  fn test_forget() {
+    let mut det = ChangeDetector::new(&TrackConfig::default());
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 0)));
+    assert!(det.check(&pp("BAW2", 51.47, 83, 270, 0, 0)));
+    // storing has failed, the next point is accepted even if unchanged
+    det.forget(["BAW1"]);
+    assert_eq!(det.len(), 1);
+    assert!(det.check(&pp("BAW1", 51.47, 83, 270, 0, 15000)));
+    assert!(!det.check(&pp("BAW2", 51.47, 83, 270, 0, 15000)));
+  }
+}
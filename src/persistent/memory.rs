@@ -0,0 +1,159 @@
+use super::{
+  retention_threshold, CleanupStats, ControllerSession, PendingPoint, SessionQuery, StoreError,
+  TrackInfo, TrackPoint, TrackQuery, TrackStore,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use std::{cmp::Reverse, collections::HashMap};
+use tokio::sync::RwLock;
+
+#[derive(Debug)]
+struct Track {
+  // time of the last point, tracks expire by it so that long
+  // flights keep their points
+  updated_at: DateTime<Utc>,
+  info: TrackInfo,
+  points: Vec<TrackPoint>,
+}
+
+#[derive(Debug)]
+pub struct MemoryStore {
+  tracks: RwLock<HashMap<String, Track>>,
+  sessions: RwLock<HashMap<String, ControllerSession>>,
+  retention: Duration,
+}
+
+impl MemoryStore {
+  pub fn new(retention: Duration) -> Self {
+    Self {
+      tracks: RwLock::new(HashMap::new()),
+      sessions: RwLock::new(HashMap::new()),
+      retention,
+    }
+  }
+}
+
+#[async_trait]
+impl TrackStore for MemoryStore {
+  async fn indexes(&self) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  async fn cleanup(&self) -> Result<CleanupStats, StoreError> {
+    let threshold = retention_threshold(self.retention);
+    let mut tracks = self.tracks.write().await;
+    let count = tracks.len();
+    let tp_count: usize = tracks.values().map(|t| t.points.len()).sum();
+    tracks.retain(|_, track| track.updated_at >= threshold);
+    let count = count - tracks.len();
+    let tp_count = tp_count - tracks.values().map(|t| t.points.len()).sum::<usize>();
+
+    let mut sessions = self.sessions.write().await;
+    let s_count = sessions.len();
+    // open sessions are kept
+    sessions.retain(|_, s| s.logoff_time.map(|t| t >= threshold).unwrap_or(true));
+    let s_count = s_count - sessions.len();
+
+    info!("{count} tracks, {tp_count} track points and {s_count} controller sessions dropped");
+    Ok(CleanupStats {
+      tracks: count as u64,
+      track_points: tp_count as u64,
+      controller_sessions: s_count as u64,
+    })
+  }
+
+  async fn counters(&self) -> Result<(u64, u64), StoreError> {
+    let tracks = self.tracks.read().await;
+    let tp_count: usize = tracks.values().map(|t| t.points.len()).sum();
+    Ok((tracks.len() as u64, tp_count as u64))
+  }
+
+  async fn ping(&self) -> Result<(), StoreError> {
+    Ok(())
+  }
+
+  async fn store_points(&self, points: Vec<PendingPoint>) -> Result<(), StoreError> {
+    let mut tracks = self.tracks.write().await;
+    let now = Utc::now();
+    for pp in points.into_iter() {
+      let track = tracks.entry(pp.info.code.clone()).or_insert_with(|| Track {
+        updated_at: now,
+        info: pp.info.clone(),
+        points: vec![],
+      });
+      // flight plans are often filed after logon
+      track.info = pp.info;
+      track.updated_at = now;
+      track.points.push(pp.point);
+    }
+    Ok(())
+  }
+
+  async fn get_track_points(
+    &self,
+    code: &str,
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
+    let tracks = self.tracks.read().await;
+    let track = tracks.get(code);
+    Ok(track.map(|track| {
+      track
+        .points
+        .iter()
+        .filter(|tp| since.map(|since| tp.ts > since).unwrap_or(true))
+        .cloned()
+        .collect()
+    }))
+  }
+
+  async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError> {
+    let tracks = self.tracks.read().await;
+    Ok(tracks.get(code).map(|track| track.info.clone()))
+  }
+
+  async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError> {
+    let tracks = self.tracks.read().await;
+    let mut res: Vec<TrackInfo> = tracks
+      .values()
+      .map(|track| &track.info)
+      .filter(|info| match query {
+        TrackQuery::Cid(cid) => info.cid == *cid,
+        TrackQuery::Callsign(callsign) => &info.callsign == callsign,
+      })
+      .cloned()
+      .collect();
+    res.sort_by_key(|info| Reverse(info.logon_time));
+    Ok(res)
+  }
+
+  async fn store_controller_sessions(
+    &self,
+    sessions: Vec<ControllerSession>,
+  ) -> Result<(), StoreError> {
+    let mut stored = self.sessions.write().await;
+    for mut session in sessions.into_iter() {
+      if let Some(s) = stored.get(&session.code) {
+        let mut atis_codes = s.atis_codes.clone();
+        std::mem::swap(&mut atis_codes, &mut session.atis_codes);
+        session.merge_atis_codes(&atis_codes);
+      }
+      stored.insert(session.code.clone(), session);
+    }
+    Ok(())
+  }
+
+  async fn find_controller_sessions(
+    &self,
+    query: &SessionQuery,
+  ) -> Result<Vec<ControllerSession>, StoreError> {
+    let sessions = self.sessions.read().await;
+    let mut res: Vec<ControllerSession> = sessions
+      .values()
+      .filter(|session| query.matches(session))
+      .cloned()
+      .collect();
+    res.sort_by_key(|session| Reverse(session.logon_time));
+    Ok(res)
+  }
+}
@@ -1,39 +1,54 @@
-use crate::{config::Config, moving::pilot::Pilot};
-use chrono::{Duration, Utc};
-use log::{error, info};
-use mongodb::{
-  bson::{doc, oid::ObjectId, DateTime},
-  options::{ClientOptions, FindOptions},
-  Client, Collection, Database, IndexModel,
+/// Track storage
+/// Pilot tracks are kept in a TrackStore which can be backed by MongoDB,
+/// SQLite or simply kept in memory for tests and short-living instances
+pub mod detect;
+pub mod memory;
+pub mod mongo;
+pub mod simplify;
+pub mod sqlite;
+pub mod writer;
+
+use self::{memory::MemoryStore, mongo::MongoStore, sqlite::SqliteStore};
+use crate::{
+  config::{Track as TrackConfig, TrackBackend},
+  moving::{controller::Controller, pilot::Pilot},
 };
-use rocket::futures::TryStreamExt;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt::Display};
 
 #[derive(Debug)]
-pub struct Persistent {
-  db: Database,
+pub enum StoreError {
+  Mongo(mongodb::error::Error),
+  Sqlite(rusqlite::Error),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Track {
-  pub _id: Option<ObjectId>,
-  pub code: String,
-  pub created_at: DateTime,
+impl Display for StoreError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StoreError::Mongo(err) => write!(f, "mongodb error: {}", err),
+      StoreError::Sqlite(err) => write!(f, "sqlite error: {}", err),
+    }
+  }
 }
 
-impl Track {
-  pub fn collection() -> &'static str {
-    "tracks"
+impl Error for StoreError {}
+
+impl From<mongodb::error::Error> for StoreError {
+  fn from(err: mongodb::error::Error) -> Self {
+    StoreError::Mongo(err)
   }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TrackPoint {
-  #[serde(skip_serializing)]
-  pub _id: Option<ObjectId>,
-  #[serde(skip_serializing)]
-  pub track_id: ObjectId,
+impl From<rusqlite::Error> for StoreError {
+  fn from(err: rusqlite::Error) -> Self {
+    StoreError::Sqlite(err)
+  }
+}
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackPoint {
   pub lat: f64,
   pub lng: f64,
   pub alt: i32,
@@ -42,151 +57,465 @@ pub struct TrackPoint {
   pub ts: i64,
 }
 
-impl TrackPoint {
-  pub fn collection() -> &'static str {
-    "track_points"
-  }
-}
-
-impl Persistent {
-  pub async fn new(cfg: &Config) -> Result<Self, mongodb::error::Error> {
-    let opts = ClientOptions::parse(&cfg.track.uri).await?;
-    let client = Client::with_options(opts)?;
-    let db = client.database(&cfg.track.dbname);
-    Ok(Self { db })
-  }
-
-  pub async fn indexes(&self) -> Result<(), mongodb::error::Error> {
-    let coll: Collection<Track> = self.db.collection(Track::collection());
-    coll
-      .create_index(
-        IndexModel::builder()
-          .keys(doc! {
-            "code": 1
-          })
-          .build(),
-        None,
-      )
-      .await?;
-
-    let coll: Collection<TrackPoint> = self.db.collection(TrackPoint::collection());
-    coll
-      .create_index(
-        IndexModel::builder()
-          .keys(doc! {
-            "track_id": 1,
-            "ts": 1,
-          })
-          .build(),
-        None,
-      )
-      .await?;
-    Ok(())
-  }
-
-  pub async fn cleanup(&self) -> Result<(), mongodb::error::Error> {
-    let coll: Collection<Track> = self.db.collection(Track::collection());
-    let dt = Utc::now() - Duration::days(2);
-    let dt = DateTime::from_chrono(dt);
-    let query = doc! {
-      "created_at": doc! {
-        "$lt": dt
-      }
-    };
-    let mut cur = coll.find(query, None).await?;
-    let mut count = 0;
-    let mut tp_count = 0;
-    while let Some(track) = cur.try_next().await? {
-      let res = self.drop_track(&track).await;
-      match res {
-        Err(err) => error!("error dropping track: {err}"),
-        Ok(cnt) => {
-          count += 1;
-          tp_count += cnt;
-        }
+impl From<&Pilot> for TrackPoint {
+  fn from(pilot: &Pilot) -> Self {
+    Self {
+      lat: pilot.position.lat,
+      lng: pilot.position.lng,
+      alt: pilot.altitude,
+      hdg: pilot.heading,
+      gs: pilot.groundspeed,
+      // feed time, so that replayed points carry their original time
+      ts: pilot.last_updated.timestamp_millis(),
+    }
+  }
+}
+
+/// Flight details stored along with the track, these make it
+/// possible to look up past flights once the pilot has gone offline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrackInfo {
+  pub code: String,
+  pub cid: u32,
+  pub callsign: String,
+  pub logon_time: DateTime<Utc>,
+  pub departure: Option<String>,
+  pub arrival: Option<String>,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+  if value.is_empty() {
+    None
+  } else {
+    Some(value.to_owned())
+  }
+}
+
+impl From<&Pilot> for TrackInfo {
+  fn from(pilot: &Pilot) -> Self {
+    let fp = pilot.flight_plan.as_ref();
+    Self {
+      code: pilot.track_code(),
+      cid: pilot.cid,
+      callsign: pilot.callsign.clone(),
+      // whole seconds as in the track code, stores don't keep nanoseconds anyway
+      logon_time: Utc.timestamp_opt(pilot.logon_time.timestamp(), 0).unwrap(),
+      departure: fp.and_then(|fp| non_empty(&fp.departure)),
+      arrival: fp.and_then(|fp| non_empty(&fp.arrival)),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum TrackQuery {
+  Cid(u32),
+  Callsign(String),
+}
+
+/// A track point on its way to the store along with the details
+/// of the track it belongs to
+#[derive(Debug, Clone)]
+pub struct PendingPoint {
+  pub info: TrackInfo,
+  pub point: TrackPoint,
+}
+
+impl From<&Pilot> for PendingPoint {
+  fn from(pilot: &Pilot) -> Self {
+    Self {
+      info: pilot.into(),
+      point: pilot.into(),
+    }
+  }
+}
+
+/// A single controller session, from logon to logoff. Sessions are
+/// identified by code, similar to pilot tracks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ControllerSession {
+  pub code: String,
+  pub cid: u32,
+  pub callsign: String,
+  pub facility: String,
+  pub freq: u32,
+  pub logon_time: DateTime<Utc>,
+  pub logoff_time: Option<DateTime<Utc>>,
+  pub atis_codes: Vec<String>,
+  pub airport: Option<String>,
+  pub fir: Option<String>,
+}
+
+impl ControllerSession {
+  pub fn new(ctrl: &Controller, airport: Option<String>, fir: Option<String>) -> Self {
+    Self {
+      code: format!(
+        "{}:{}:{}",
+        ctrl.cid,
+        ctrl.callsign,
+        ctrl.logon_time.timestamp()
+      ),
+      cid: ctrl.cid,
+      callsign: ctrl.callsign.clone(),
+      facility: ctrl.facility.to_string(),
+      freq: ctrl.freq,
+      logon_time: Utc.timestamp_opt(ctrl.logon_time.timestamp(), 0).unwrap(),
+      logoff_time: None,
+      atis_codes: non_empty(&ctrl.atis_code).into_iter().collect(),
+      airport,
+      fir,
+    }
+  }
+
+  // keeps the order codes were seen in
+  pub fn merge_atis_codes(&mut self, codes: &[String]) {
+    for code in codes {
+      if !self.atis_codes.contains(code) {
+        self.atis_codes.push(code.clone());
       }
     }
-    info!("{count} tracks and {tp_count} track points dropped");
-    Ok(())
   }
+}
+
+/// Controller sessions lookup, sessions matching all the given
+/// conditions are returned
+#[derive(Debug, Clone, Default)]
+pub struct SessionQuery {
+  pub airport: Option<String>,
+  pub fir: Option<String>,
+}
+
+impl SessionQuery {
+  pub fn matches(&self, session: &ControllerSession) -> bool {
+    let airport_ok = self.airport.is_none() || self.airport == session.airport;
+    let fir_ok = self.fir.is_none() || self.fir == session.fir;
+    airport_ok && fir_ok
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupStats {
+  pub tracks: u64,
+  pub track_points: u64,
+  pub controller_sessions: u64,
+}
+
+// objects created before this moment are considered outdated
+fn retention_threshold(retention: Duration) -> DateTime<Utc> {
+  Utc::now() - retention
+}
+
+#[async_trait]
+pub trait TrackStore: std::fmt::Debug + Send + Sync {
+  async fn indexes(&self) -> Result<(), StoreError>;
+  async fn cleanup(&self) -> Result<CleanupStats, StoreError>;
+  // true if the backend expires outdated objects by itself, i.e. with
+  // mongodb TTL indexes, so that periodic cleanups are unnecessary
+  fn expires_automatically(&self) -> bool {
+    false
+  }
+  async fn counters(&self) -> Result<(u64, u64), StoreError>;
+  // checks that the backend is reachable
+  async fn ping(&self) -> Result<(), StoreError>;
+  async fn store_points(&self, points: Vec<PendingPoint>) -> Result<(), StoreError>;
+  async fn store_track(&self, pilot: &Pilot) -> Result<(), StoreError> {
+    self.store_points(vec![pilot.into()]).await
+  }
+  async fn get_track_points(
+    &self,
+    code: &str,
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError>;
+  async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError>;
+  // most recent flights first
+  async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError>;
+  // sessions are upserted by code, atis codes are merged with the stored ones
+  async fn store_controller_sessions(
+    &self,
+    sessions: Vec<ControllerSession>,
+  ) -> Result<(), StoreError>;
+  // most recent sessions first
+  async fn find_controller_sessions(
+    &self,
+    query: &SessionQuery,
+  ) -> Result<Vec<ControllerSession>, StoreError>;
+}
+
+pub async fn connect(cfg: &TrackConfig) -> Result<Box<dyn TrackStore>, StoreError> {
+  // we don't expect retention values chrono can't handle
+  let retention = Duration::from_std(cfg.retention).unwrap();
+  let store: Box<dyn TrackStore> = match cfg.backend {
+    TrackBackend::Mongo => {
+      let max_interval = Duration::from_std(cfg.max_interval).unwrap();
+      Box::new(MongoStore::new(&cfg.uri, &cfg.dbname, retention, max_interval).await?)
+    }
+    TrackBackend::Sqlite => Box::new(SqliteStore::new(&cfg.path, retention)?),
+    TrackBackend::Memory => Box::new(MemoryStore::new(retention)),
+  };
+  Ok(store)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    moving::{
+      controller::Facility,
+      pilot::{FlightPlan, Pilot},
+    },
+    types::Point,
+  };
+  use tokio::time::{sleep, Duration as TDuration};
 
-  pub async fn counters(&self) -> Result<(u64, u64), mongodb::error::Error> {
-    let coll: Collection<TrackPoint> = self.db.collection(TrackPoint::collection());
-    let tp_count = coll.estimated_document_count(None).await?;
-    let coll: Collection<Track> = self.db.collection(Track::collection());
-    let t_count = coll.estimated_document_count(None).await?;
-    Ok((t_count, tp_count))
+  pub fn test_flight_plan(departure: &str, arrival: &str) -> FlightPlan {
+    FlightPlan {
+      flight_rules: "I".into(),
+      aircraft: "A320".into(),
+      departure: departure.into(),
+      arrival: arrival.into(),
+      alternate: "".into(),
+      cruise_tas: 450,
+      altitude: 35000,
+      deptime: "1200".into(),
+      enroute_time: "0100".into(),
+      fuel_time: "0300".into(),
+      remarks: "".into(),
+      route: "DCT".into(),
+    }
   }
 
-  pub async fn drop_track(&self, track: &Track) -> Result<u64, mongodb::error::Error> {
-    let coll: Collection<TrackPoint> = self.db.collection(TrackPoint::collection());
-    let query = doc! {"track_id": track._id.unwrap() };
-    let del_res = coll.delete_many(query, None).await?;
-    let tp_count = del_res.deleted_count;
+  pub fn test_controller(callsign: &str, facility: Facility) -> Controller {
+    let now = Utc::now();
+    Controller {
+      cid: 1000001,
+      name: "Jane Doe".into(),
+      callsign: callsign.into(),
+      freq: 118500,
+      facility,
+      rating: 5,
+      server: "UK".into(),
+      visual_range: 50,
+      atis_code: "A".into(),
+      text_atis: "".into(),
+      human_readable: None,
+      last_updated: now,
+      logon_time: now,
+    }
+  }
 
-    let coll: Collection<Track> = self.db.collection(Track::collection());
-    let query = doc! {"_id": track._id.unwrap() };
-    coll.delete_one(query, None).await?;
-    Ok(tp_count)
+  pub fn test_pilot(callsign: &str) -> Pilot {
+    Pilot {
+      cid: 1000000,
+      name: "John Doe".into(),
+      callsign: callsign.into(),
+      server: "GERMANY".into(),
+      pilot_rating: 0,
+      position: Point {
+        lat: 51.47,
+        lng: -0.45,
+      },
+      altitude: 83,
+      groundspeed: 0,
+      transponder: "2000".into(),
+      heading: 270,
+      qnh_i_hg: 2992,
+      qnh_mb: 1013,
+      flight_plan: None,
+      logon_time: Utc::now(),
+      last_updated: Utc::now(),
+      aircraft_type: None,
+      fir: None,
+      controlled_by: None,
+      phase: None,
+      progress: None,
+    }
   }
 
-  pub async fn store_track(&self, pilot: &Pilot) -> Result<(), mongodb::error::Error> {
-    let now = DateTime::now();
-    let coll: Collection<Track> = self.db.collection(Track::collection());
+  async fn check_store(store: &dyn TrackStore) {
+    store.indexes().await.unwrap();
+    store.ping().await.unwrap();
+
+    let mut pilot = test_pilot("BAW123");
     let code = pilot.track_code();
-    let track = coll.find_one(doc! { "code": code.clone() }, None).await?;
-    let track_id = if track.is_none() {
-      let d = doc! {
-        "code": code,
-        "created_at": now
-      };
-      let res = self
-        .db
-        .collection(Track::collection())
-        .insert_one(d, None)
-        .await?;
-      res.inserted_id.as_object_id().unwrap()
-    } else {
-      track.unwrap()._id.unwrap()
+    let res = store.get_track_points(&code, None).await.unwrap();
+    assert!(res.is_none());
+    let res = store.get_track_info(&code).await.unwrap();
+    assert!(res.is_none());
+
+    store.store_track(&pilot).await.unwrap();
+    pilot.altitude = 3000;
+    store.store_track(&pilot).await.unwrap();
+    store.store_track(&test_pilot("AFR456")).await.unwrap();
+
+    let tps = store.get_track_points(&code, None).await.unwrap().unwrap();
+    assert_eq!(tps.len(), 2);
+    assert_eq!(tps[0].alt, 83);
+    assert_eq!(tps[1].alt, 3000);
+
+    let tps = store
+      .get_track_points(&code, Some(tps[1].ts))
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(tps.is_empty());
+
+    let (tc, tpc) = store.counters().await.unwrap();
+    assert_eq!(tc, 2);
+    assert_eq!(tpc, 3);
+
+    // the flight plan filed after logon gets to the track
+    pilot.flight_plan = Some(test_flight_plan("EGLL", "LFPG"));
+    store.store_track(&pilot).await.unwrap();
+    let info = store.get_track_info(&code).await.unwrap().unwrap();
+    assert_eq!(info, (&pilot).into());
+    assert_eq!(info.departure.as_deref(), Some("EGLL"));
+    assert_eq!(info.arrival.as_deref(), Some("LFPG"));
+
+    let res = store
+      .find_tracks(&TrackQuery::Cid(pilot.cid))
+      .await
+      .unwrap();
+    assert_eq!(res.len(), 2);
+    let res = store
+      .find_tracks(&TrackQuery::Callsign("BAW123".into()))
+      .await
+      .unwrap();
+    assert_eq!(res, vec![info]);
+    let res = store.find_tracks(&TrackQuery::Cid(1)).await.unwrap();
+    assert!(res.is_empty());
+
+    // nothing is old enough to be dropped
+    let stats = store.cleanup().await.unwrap();
+    assert_eq!(stats, CleanupStats::default());
+    let (tc, tpc) = store.counters().await.unwrap();
+    assert_eq!(tc, 2);
+    assert_eq!(tpc, 4);
+  }
+
+  async fn check_sessions(store: &dyn TrackStore) {
+    let ctrl = test_controller("EGLL_TWR", Facility::Tower);
+    let mut twr = ControllerSession::new(&ctrl, Some("EGLL".into()), Some("EGTT".into()));
+    let ctrl = test_controller("EGLL_ATIS", Facility::ATIS);
+    let mut atis = ControllerSession::new(&ctrl, Some("EGLL".into()), Some("EGTT".into()));
+    atis.logon_time -= Duration::hours(1);
+    let ctrl = test_controller("LON_CTR", Facility::Radar);
+    let ctr = ControllerSession::new(&ctrl, None, Some("EGTT".into()));
+    store
+      .store_controller_sessions(vec![twr.clone(), atis.clone(), ctr.clone()])
+      .await
+      .unwrap();
+
+    // ATIS codes seen before are kept
+    atis.atis_codes = vec!["B".into()];
+    twr.logoff_time = Some(
+      Utc
+        .timestamp_opt(twr.logon_time.timestamp() + 3600, 0)
+        .unwrap(),
+    );
+    store
+      .store_controller_sessions(vec![twr.clone(), atis.clone()])
+      .await
+      .unwrap();
+    atis.atis_codes = vec!["A".into(), "B".into()];
+
+    let query = SessionQuery {
+      airport: Some("EGLL".into()),
+      fir: None,
     };
+    let res = store.find_controller_sessions(&query).await.unwrap();
+    assert_eq!(res, vec![twr.clone(), atis.clone()]);
 
-    let point = doc! {
-      "track_id": track_id,
-      "lat": pilot.position.lat,
-      "lng": pilot.position.lng,
-      "alt": pilot.altitude,
-      "hdg": pilot.heading as i32,
-      "gs": pilot.groundspeed,
-      "ts": now.timestamp_millis(),
+    let query = SessionQuery {
+      airport: None,
+      fir: Some("EGTT".into()),
     };
-    let coll = self.db.collection(TrackPoint::collection());
-    coll.insert_one(point, None).await?;
-    Ok(())
+    let res = store.find_controller_sessions(&query).await.unwrap();
+    assert_eq!(res.len(), 3);
+
+    let query = SessionQuery {
+      airport: Some("EGLL".into()),
+      fir: Some("LFFF".into()),
+    };
+    let res = store.find_controller_sessions(&query).await.unwrap();
+    assert!(res.is_empty());
   }
 
-  async fn get_track_by_code(&self, code: &str) -> Result<Option<Track>, mongodb::error::Error> {
-    let query = doc! {"code": code};
-    let coll: Collection<Track> = self.db.collection(Track::collection());
-    coll.find_one(query, None).await
+  async fn check_store_retention(store: &dyn TrackStore) {
+    store.store_track(&test_pilot("BAW123")).await.unwrap();
+    store.store_track(&test_pilot("AFR456")).await.unwrap();
+    let ctrl = test_controller("EGLL_TWR", Facility::Tower);
+    let mut closed = ControllerSession::new(&ctrl, Some("EGLL".into()), None);
+    closed.logoff_time = Some(closed.logon_time);
+    // sessions expire once they've ended, the open one is kept
+    let ctrl = test_controller("EGLL_GND", Facility::Ground);
+    let open = ControllerSession::new(&ctrl, Some("EGLL".into()), None);
+    store
+      .store_controller_sessions(vec![closed, open.clone()])
+      .await
+      .unwrap();
+    let stats = store.cleanup().await.unwrap();
+    assert_eq!(
+      stats,
+      CleanupStats {
+        tracks: 2,
+        track_points: 2,
+        controller_sessions: 1,
+      }
+    );
+    let res = store
+      .find_controller_sessions(&SessionQuery::default())
+      .await
+      .unwrap();
+    assert_eq!(res, vec![open]);
+    let (tc, tpc) = store.counters().await.unwrap();
+    assert_eq!(tc, 0);
+    assert_eq!(tpc, 0);
   }
 
-  pub async fn get_track_points(
-    &self,
-    pilot: &Pilot,
-  ) -> Result<Option<Vec<TrackPoint>>, mongodb::error::Error> {
-    let track = self.get_track_by_code(&pilot.track_code()).await?;
-    if let Some(track) = track {
-      let track_id = track._id.unwrap();
-      let coll: Collection<TrackPoint> = self.db.collection(TrackPoint::collection());
-      let opts = FindOptions::builder().sort(doc! {"ts": 1}).build();
-      let mut cur = coll.find(doc! {"track_id": track_id}, opts).await?;
-      let mut tps = vec![];
-      while let Some(tp) = cur.try_next().await? {
-        tps.push(tp);
+  // tracks expire by their last point, not by the time they were created
+  async fn check_track_expiry(store: &dyn TrackStore) {
+    let mut pilot = test_pilot("BAW123");
+    store.store_track(&pilot).await.unwrap();
+    store.store_track(&test_pilot("AFR456")).await.unwrap();
+    sleep(TDuration::from_millis(300)).await;
+    pilot.altitude = 3000;
+    store.store_track(&pilot).await.unwrap();
+
+    let stats = store.cleanup().await.unwrap();
+    assert_eq!(
+      stats,
+      CleanupStats {
+        tracks: 1,
+        track_points: 1,
+        controller_sessions: 0,
       }
-      Ok(Some(tps))
-    } else {
-      Ok(None)
-    }
+    );
+    let tps = store
+      .get_track_points(&pilot.track_code(), None)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(tps.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_memory_store() {
+    let store = MemoryStore::new(Duration::days(2));
+    check_store(&store).await;
+    check_sessions(&store).await;
+    let store = MemoryStore::new(Duration::seconds(-1));
+    check_store_retention(&store).await;
+    let store = MemoryStore::new(Duration::milliseconds(200));
+    check_track_expiry(&store).await;
+  }
+
+  #[tokio::test]
+  async fn test_sqlite_store() {
+    let store = SqliteStore::new(":memory:", Duration::days(2)).unwrap();
+    check_store(&store).await;
+    check_sessions(&store).await;
+    let store = SqliteStore::new(":memory:", Duration::seconds(-1)).unwrap();
+    check_store_retention(&store).await;
+    let store = SqliteStore::new(":memory:", Duration::milliseconds(200)).unwrap();
+    check_track_expiry(&store).await;
   }
 }
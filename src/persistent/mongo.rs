@@ -0,0 +1,551 @@
+use super::{
+  retention_threshold, CleanupStats, ControllerSession, PendingPoint, SessionQuery, StoreError,
+  TrackInfo, TrackPoint, TrackQuery, TrackStore,
+};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use mongodb::{
+  bson::{doc, oid::ObjectId, DateTime, Document},
+  options::{ClientOptions, FindOptions, IndexOptions, UpdateOptions},
+  Client, Collection, Database, IndexModel,
+};
+use rocket::futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+#[derive(Debug)]
+struct CachedTrack {
+  track_id: ObjectId,
+  info: TrackInfo,
+  // when the track document was last found or created
+  checked_at: chrono::DateTime<Utc>,
+  // when the last point of the track was stored
+  seen_at: chrono::DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct MongoStore {
+  db: Database,
+  // track ids by track code, saves a find_one() per point
+  track_ids: RwLock<HashMap<String, CachedTrack>>,
+  retention: Duration,
+  max_interval: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Track {
+  pub _id: Option<ObjectId>,
+  pub code: String,
+  pub created_at: DateTime,
+  // time of the last stored point, tracks expire by it so that
+  // they outlive their points
+  #[serde(default)]
+  pub updated_at: Option<DateTime>,
+  // flight details are missing in tracks stored by older versions
+  #[serde(default)]
+  pub cid: Option<i64>,
+  #[serde(default)]
+  pub callsign: Option<String>,
+  #[serde(default)]
+  pub logon_time: Option<DateTime>,
+  #[serde(default)]
+  pub departure: Option<String>,
+  #[serde(default)]
+  pub arrival: Option<String>,
+}
+
+impl Track {
+  pub fn collection() -> &'static str {
+    "tracks"
+  }
+
+  pub fn info(&self) -> Option<TrackInfo> {
+    match (self.cid, &self.callsign, self.logon_time) {
+      (Some(cid), Some(callsign), Some(logon_time)) => Some(TrackInfo {
+        code: self.code.clone(),
+        cid: cid as u32,
+        callsign: callsign.clone(),
+        logon_time: logon_time.to_chrono(),
+        departure: self.departure.clone(),
+        arrival: self.arrival.clone(),
+      }),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+  pub code: String,
+  pub cid: i64,
+  pub callsign: String,
+  pub facility: String,
+  pub freq: i64,
+  pub logon_time: DateTime,
+  pub logoff_time: Option<DateTime>,
+  pub atis_codes: Vec<String>,
+  pub airport: Option<String>,
+  pub fir: Option<String>,
+}
+
+impl Session {
+  pub fn collection() -> &'static str {
+    "controller_sessions"
+  }
+}
+
+impl From<Session> for ControllerSession {
+  fn from(s: Session) -> Self {
+    Self {
+      code: s.code,
+      cid: s.cid as u32,
+      callsign: s.callsign,
+      facility: s.facility,
+      freq: s.freq as u32,
+      logon_time: s.logon_time.to_chrono(),
+      logoff_time: s.logoff_time.map(|t| t.to_chrono()),
+      atis_codes: s.atis_codes,
+      airport: s.airport,
+      fir: s.fir,
+    }
+  }
+}
+
+fn info_doc(info: &TrackInfo) -> Document {
+  doc! {
+    "cid": info.cid as i64,
+    "callsign": &info.callsign,
+    "logon_time": DateTime::from_chrono(info.logon_time),
+    "departure": &info.departure,
+    "arrival": &info.arrival,
+  }
+}
+
+fn track_points_collection() -> &'static str {
+  "track_points"
+}
+
+async fn ensure_ttl_index<T>(
+  coll: &Collection<T>,
+  field: &str,
+  ttl: std::time::Duration,
+) -> Result<(), mongodb::error::Error> {
+  let name = format!("{field}_ttl");
+  let model = || {
+    IndexModel::builder()
+      .keys(doc! { field: 1 })
+      .options(
+        IndexOptions::builder()
+          .name(name.clone())
+          .expire_after(ttl)
+          .build(),
+      )
+      .build()
+  };
+
+  let res = coll.create_index(model(), None).await;
+  if let Err(err) = res {
+    // most likely the index exists with a different ttl as the retention
+    // setting has changed, mongodb doesn't allow to redefine indexes in place
+    warn!("error creating {name} index, recreating: {err}");
+    coll.drop_index(&name, None).await?;
+    coll.create_index(model(), None).await?;
+  }
+  Ok(())
+}
+
+impl MongoStore {
+  pub async fn new(
+    uri: &str,
+    dbname: &str,
+    retention: Duration,
+    max_interval: Duration,
+  ) -> Result<Self, mongodb::error::Error> {
+    let opts = ClientOptions::parse(uri).await?;
+    let client = Client::with_options(opts)?;
+    let db = client.database(dbname);
+    Ok(Self {
+      db,
+      track_ids: RwLock::new(HashMap::new()),
+      retention,
+      max_interval,
+    })
+  }
+
+  async fn get_track_id(
+    &self,
+    info: &TrackInfo,
+    now: DateTime,
+  ) -> Result<ObjectId, mongodb::error::Error> {
+    let chrono_now = now.to_chrono();
+    if let Some(cached) = self.track_ids.write().await.get_mut(&info.code) {
+      // flight details are the same, no need to update the track unless
+      // it's old enough to have been dropped by the TTL monitor meanwhile
+      if &cached.info == info && chrono_now - cached.checked_at < self.retention {
+        cached.seen_at = chrono_now;
+        return Ok(cached.track_id);
+      }
+    }
+
+    let coll: Collection<Document> = self.db.collection(Track::collection());
+    let track = self.get_track_by_code(&info.code).await?;
+    let track_id = if let Some(track) = track {
+      let track_id = track._id.unwrap();
+      if track.info().as_ref() != Some(info) {
+        coll
+          .update_one(doc! {"_id": track_id}, doc! {"$set": info_doc(info)}, None)
+          .await?;
+      }
+      track_id
+    } else {
+      let mut d = doc! {
+        "code": &info.code,
+        "created_at": now,
+        "updated_at": now,
+      };
+      d.extend(info_doc(info));
+      let res = coll.insert_one(d, None).await?;
+      res.inserted_id.as_object_id().unwrap()
+    };
+    self.track_ids.write().await.insert(
+      info.code.clone(),
+      CachedTrack {
+        track_id,
+        info: info.clone(),
+        checked_at: chrono_now,
+        seen_at: chrono_now,
+      },
+    );
+    Ok(track_id)
+  }
+
+  /// Forgets cached ids of tracks which haven't stored anything for a
+  /// while. Active tracks store a point at least every max_interval so
+  /// these belong to pilots who went offline.
+  async fn purge_track_ids(&self, now: chrono::DateTime<Utc>) {
+    let threshold = now - self.max_interval * 2;
+    self
+      .track_ids
+      .write()
+      .await
+      .retain(|_, cached| cached.seen_at >= threshold);
+  }
+
+  async fn get_track_by_code(&self, code: &str) -> Result<Option<Track>, mongodb::error::Error> {
+    let query = doc! {"code": code};
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    coll.find_one(query, None).await
+  }
+}
+
+#[async_trait]
+impl TrackStore for MongoStore {
+  async fn indexes(&self) -> Result<(), StoreError> {
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    coll
+      .create_index(
+        IndexModel::builder()
+          .keys(doc! {
+            "code": 1
+          })
+          .build(),
+        None,
+      )
+      .await?;
+
+    coll
+      .create_index(
+        IndexModel::builder()
+          .keys(doc! {
+            "cid": 1,
+            "logon_time": -1,
+          })
+          .build(),
+        None,
+      )
+      .await?;
+    coll
+      .create_index(
+        IndexModel::builder()
+          .keys(doc! {
+            "callsign": 1,
+            "logon_time": -1,
+          })
+          .build(),
+        None,
+      )
+      .await?;
+
+    let coll: Collection<TrackPoint> = self.db.collection(track_points_collection());
+    coll
+      .create_index(
+        IndexModel::builder()
+          .keys(doc! {
+            "track_id": 1,
+            "ts": 1,
+          })
+          .build(),
+        None,
+      )
+      .await?;
+
+    let coll: Collection<Session> = self.db.collection(Session::collection());
+    coll
+      .create_index(
+        IndexModel::builder()
+          .keys(doc! {
+            "code": 1
+          })
+          .options(IndexOptions::builder().unique(true).build())
+          .build(),
+        None,
+      )
+      .await?;
+    for field in ["airport", "fir"] {
+      coll
+        .create_index(
+          IndexModel::builder()
+            .keys(doc! {
+              field: 1,
+              "logon_time": -1,
+            })
+            .build(),
+          None,
+        )
+        .await?;
+    }
+
+    let ttl = self.retention.to_std().unwrap_or_default();
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    // tracks used to expire by created_at leaving the recent points of
+    // long flights orphaned, they expire by the last point time now
+    if coll.drop_index("created_at_ttl", None).await.is_ok() {
+      info!("tracks created_at_ttl index dropped");
+    }
+    coll
+      .update_many(
+        doc! {"updated_at": {"$exists": false}},
+        vec![doc! {"$set": {"updated_at": "$created_at"}}],
+        None,
+      )
+      .await?;
+    ensure_ttl_index(&coll, "updated_at", ttl).await?;
+    let coll: Collection<TrackPoint> = self.db.collection(track_points_collection());
+    ensure_ttl_index(&coll, "created_at", ttl).await?;
+    let coll: Collection<Session> = self.db.collection(Session::collection());
+    // same for controller sessions, these expire once they've ended and
+    // documents without logoff_time, i.e. open sessions, are left alone
+    if coll.drop_index("created_at_ttl", None).await.is_ok() {
+      info!("controller sessions created_at_ttl index dropped");
+    }
+    ensure_ttl_index(&coll, "logoff_time", ttl).await?;
+    Ok(())
+  }
+
+  async fn cleanup(&self) -> Result<CleanupStats, StoreError> {
+    // TTL indexes do the job in background, however points stored
+    // without created_at field by older versions still have to be dropped
+    let threshold = retention_threshold(self.retention);
+    let bson_threshold = DateTime::from_chrono(threshold);
+
+    // tracks are dropped along with all their points
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    let query = doc! {
+      "$or": [
+        {"updated_at": {"$lt": bson_threshold}},
+        {"updated_at": {"$exists": false}, "created_at": {"$lt": bson_threshold}},
+      ]
+    };
+    let mut cur = coll.find(query.clone(), None).await?;
+    let mut track_ids = vec![];
+    while let Some(track) = cur.try_next().await? {
+      track_ids.extend(track._id);
+    }
+    let count = coll.delete_many(query, None).await?.deleted_count;
+
+    let coll: Collection<TrackPoint> = self.db.collection(track_points_collection());
+    let query = doc! {
+      "$or": [
+        {"track_id": {"$in": &track_ids}},
+        {"created_at": {"$exists": false}, "ts": {"$lt": threshold.timestamp_millis()}},
+      ]
+    };
+    let tp_count = coll.delete_many(query, None).await?.deleted_count;
+
+    let coll: Collection<Session> = self.db.collection(Session::collection());
+    let query = doc! {
+      "logoff_time": doc! {
+        "$lt": bson_threshold
+      }
+    };
+    let s_count = coll.delete_many(query, None).await?.deleted_count;
+
+    // some of the cached ids might belong to the dropped tracks
+    self.track_ids.write().await.clear();
+    info!("{count} tracks, {tp_count} track points and {s_count} controller sessions dropped");
+    Ok(CleanupStats {
+      tracks: count,
+      track_points: tp_count,
+      controller_sessions: s_count,
+    })
+  }
+
+  fn expires_automatically(&self) -> bool {
+    true
+  }
+
+  async fn counters(&self) -> Result<(u64, u64), StoreError> {
+    let coll: Collection<TrackPoint> = self.db.collection(track_points_collection());
+    let tp_count = coll.estimated_document_count(None).await?;
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    let t_count = coll.estimated_document_count(None).await?;
+    Ok((t_count, tp_count))
+  }
+
+  async fn ping(&self) -> Result<(), StoreError> {
+    self.db.run_command(doc! { "ping": 1 }, None).await?;
+    Ok(())
+  }
+
+  async fn store_points(&self, points: Vec<PendingPoint>) -> Result<(), StoreError> {
+    let now = DateTime::now();
+    let mut docs = vec![];
+    let mut track_ids = vec![];
+    for pp in points.into_iter() {
+      let track_id = self.get_track_id(&pp.info, now).await?;
+      if !track_ids.contains(&track_id) {
+        track_ids.push(track_id);
+      }
+      let tp = pp.point;
+      docs.push(doc! {
+        "track_id": track_id,
+        "lat": tp.lat,
+        "lng": tp.lng,
+        "alt": tp.alt,
+        "hdg": tp.hdg as i32,
+        "gs": tp.gs,
+        "ts": tp.ts,
+        "created_at": now,
+      });
+    }
+
+    if !docs.is_empty() {
+      let coll = self.db.collection(track_points_collection());
+      coll.insert_many(docs, None).await?;
+      let coll: Collection<Track> = self.db.collection(Track::collection());
+      coll
+        .update_many(
+          doc! {"_id": {"$in": &track_ids}},
+          doc! {"$set": {"updated_at": now}},
+          None,
+        )
+        .await?;
+    }
+    self.purge_track_ids(now.to_chrono()).await;
+    Ok(())
+  }
+
+  async fn get_track_points(
+    &self,
+    code: &str,
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
+    let track = self.get_track_by_code(code).await?;
+    if let Some(track) = track {
+      let track_id = track._id.unwrap();
+      let coll: Collection<TrackPoint> = self.db.collection(track_points_collection());
+      let opts = FindOptions::builder().sort(doc! {"ts": 1}).build();
+      let query = if let Some(since) = since {
+        doc! {"track_id": track_id, "ts": doc! {"$gt": since}}
+      } else {
+        doc! {"track_id": track_id}
+      };
+      let mut cur = coll.find(query, opts).await?;
+      let mut tps = vec![];
+      while let Some(tp) = cur.try_next().await? {
+        tps.push(tp);
+      }
+      Ok(Some(tps))
+    } else {
+      Ok(None)
+    }
+  }
+
+  async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError> {
+    let track = self.get_track_by_code(code).await?;
+    Ok(track.and_then(|track| track.info()))
+  }
+
+  async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError> {
+    let query = match query {
+      TrackQuery::Cid(cid) => doc! {"cid": *cid as i64},
+      TrackQuery::Callsign(callsign) => doc! {"callsign": callsign},
+    };
+    let coll: Collection<Track> = self.db.collection(Track::collection());
+    let opts = FindOptions::builder().sort(doc! {"logon_time": -1}).build();
+    let mut cur = coll.find(query, opts).await?;
+    let mut res = vec![];
+    while let Some(track) = cur.try_next().await? {
+      if let Some(info) = track.info() {
+        res.push(info);
+      }
+    }
+    Ok(res)
+  }
+
+  async fn store_controller_sessions(
+    &self,
+    sessions: Vec<ControllerSession>,
+  ) -> Result<(), StoreError> {
+    let coll: Collection<Session> = self.db.collection(Session::collection());
+    let now = DateTime::now();
+    for session in sessions.into_iter() {
+      let update = doc! {
+        "$set": {
+          "cid": session.cid as i64,
+          "callsign": &session.callsign,
+          "facility": &session.facility,
+          "freq": session.freq as i64,
+          "logon_time": DateTime::from_chrono(session.logon_time),
+          "logoff_time": session.logoff_time.map(DateTime::from_chrono),
+          "airport": &session.airport,
+          "fir": &session.fir,
+        },
+        "$setOnInsert": {
+          "created_at": now,
+        },
+        "$addToSet": {
+          "atis_codes": { "$each": &session.atis_codes },
+        },
+      };
+      let opts = UpdateOptions::builder().upsert(true).build();
+      coll
+        .update_one(doc! {"code": &session.code}, update, opts)
+        .await?;
+    }
+    Ok(())
+  }
+
+  async fn find_controller_sessions(
+    &self,
+    query: &SessionQuery,
+  ) -> Result<Vec<ControllerSession>, StoreError> {
+    let mut filter = doc! {};
+    if let Some(airport) = &query.airport {
+      filter.insert("airport", airport);
+    }
+    if let Some(fir) = &query.fir {
+      filter.insert("fir", fir);
+    }
+    let coll: Collection<Session> = self.db.collection(Session::collection());
+    let opts = FindOptions::builder().sort(doc! {"logon_time": -1}).build();
+    let mut cur = coll.find(filter, opts).await?;
+    let mut res = vec![];
+    while let Some(session) = cur.try_next().await? {
+      res.push(session.into());
+    }
+    Ok(res)
+  }
+}
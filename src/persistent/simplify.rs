@@ -0,0 +1,211 @@
+/// Track simplification
+/// This is a Douglas-Peucker variant driven by a point budget rather than
+/// by a fixed tolerance: the most significant points are picked one by one
+/// until the budget is exhausted. Significance takes into account not only
+/// the cross-track distance but altitude and heading deviations as well,
+/// so step climbs and turns survive simplification.
+use super::TrackPoint;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+const NM_PER_DEGREE: f64 = 60.0;
+// 1000ft of altitude deviation weighs as much as 1nm of cross-track distance
+const ALT_FT_PER_NM: f64 = 1000.0;
+// 30 degrees of heading deviation weighs as much as 1nm of cross-track distance
+const HDG_DEG_PER_NM: f64 = 30.0;
+// anything less than 2 would lose either the first or the last point
+const MIN_POINTS: usize = 2;
+
+#[derive(Debug)]
+struct Segment {
+  start: usize,
+  end: usize,
+  idx: usize,
+  error: f64,
+}
+
+impl PartialEq for Segment {
+  fn eq(&self, other: &Self) -> bool {
+    self.error.total_cmp(&other.error) == Ordering::Equal
+  }
+}
+
+impl Eq for Segment {}
+
+impl PartialOrd for Segment {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Segment {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.error.total_cmp(&other.error)
+  }
+}
+
+fn lng_delta(a: f64, b: f64) -> f64 {
+  // shortest signed distance between longitudes, to stay sane across 180
+  (b - a + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn hdg_delta(a: f64, b: f64) -> f64 {
+  lng_delta(a, b).abs()
+}
+
+// projects tp onto a flat plane with its origin at the origin point, in nm
+fn project(origin: &TrackPoint, tp: &TrackPoint) -> (f64, f64) {
+  let mean_lat = ((origin.lat + tp.lat) / 2.0).to_radians();
+  let x = lng_delta(origin.lng, tp.lng) * NM_PER_DEGREE * mean_lat.cos();
+  let y = (tp.lat - origin.lat) * NM_PER_DEGREE;
+  (x, y)
+}
+
+fn deviation(a: &TrackPoint, b: &TrackPoint, p: &TrackPoint) -> f64 {
+  let (bx, by) = project(a, b);
+  let (px, py) = project(a, p);
+
+  let seg_len_sq = bx * bx + by * by;
+  let (xtrack, frac) = if seg_len_sq == 0.0 {
+    ((px * px + py * py).sqrt(), 0.0)
+  } else {
+    let frac = ((px * bx + py * by) / seg_len_sq).clamp(0.0, 1.0);
+    let dx = px - bx * frac;
+    let dy = py - by * frac;
+    ((dx * dx + dy * dy).sqrt(), frac)
+  };
+
+  let frac = if b.ts > a.ts {
+    (p.ts - a.ts) as f64 / (b.ts - a.ts) as f64
+  } else {
+    frac
+  };
+  let alt = a.alt as f64 + (b.alt - a.alt) as f64 * frac;
+  let alt_dev = (p.alt as f64 - alt).abs() / ALT_FT_PER_NM;
+
+  let hdg_dev = if seg_len_sq == 0.0 {
+    hdg_delta(a.hdg as f64, p.hdg as f64)
+  } else {
+    let bearing = bx.atan2(by).to_degrees().rem_euclid(360.0);
+    hdg_delta(bearing, p.hdg as f64)
+  } / HDG_DEG_PER_NM;
+
+  xtrack.max(alt_dev).max(hdg_dev)
+}
+
+fn make_segment(points: &[TrackPoint], start: usize, end: usize) -> Option<Segment> {
+  if end - start < 2 {
+    return None;
+  }
+  let a = &points[start];
+  let b = &points[end];
+  let mut segment = Segment {
+    start,
+    end,
+    idx: start + 1,
+    error: f64::MIN,
+  };
+  for (idx, p) in points.iter().enumerate().take(end).skip(start + 1) {
+    let error = deviation(a, b, p);
+    if error > segment.error {
+      segment.error = error;
+      segment.idx = idx;
+    }
+  }
+  Some(segment)
+}
+
+pub fn simplify(points: Vec<TrackPoint>, max_points: usize) -> Vec<TrackPoint> {
+  let max_points = max_points.max(MIN_POINTS);
+  if points.len() <= max_points {
+    return points;
+  }
+
+  let last = points.len() - 1;
+  let mut keep = vec![false; points.len()];
+  keep[0] = true;
+  keep[last] = true;
+  let mut kept = MIN_POINTS;
+
+  let mut heap = BinaryHeap::new();
+  if let Some(segment) = make_segment(&points, 0, last) {
+    heap.push(segment);
+  }
+
+  while kept < max_points {
+    let segment = match heap.pop() {
+      Some(segment) => segment,
+      None => break,
+    };
+    keep[segment.idx] = true;
+    kept += 1;
+    if let Some(left) = make_segment(&points, segment.start, segment.idx) {
+      heap.push(left);
+    }
+    if let Some(right) = make_segment(&points, segment.idx, segment.end) {
+      heap.push(right);
+    }
+  }
+
+  points
+    .into_iter()
+    .zip(keep)
+    .filter(|(_, keep)| *keep)
+    .map(|(tp, _)| tp)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tp(lat: f64, lng: f64, alt: i32, hdg: i16, ts: i64) -> TrackPoint {
+    TrackPoint {
+      lat,
+      lng,
+      alt,
+      hdg,
+      gs: 450,
+      ts,
+    }
+  }
+
+  #[test]
+  fn test_simplify_corner() {
+    // flying east, then turning north at (0, 5)
+    let mut points = vec![];
+    for i in 0..=5 {
+      points.push(tp(0.0, i as f64, 35000, 90, i));
+    }
+    for i in 1..=5 {
+      points.push(tp(i as f64, 5.0, 35000, 0, 5 + i));
+    }
+
+    let res = simplify(points, 3);
+    assert_eq!(res.len(), 3);
+    assert_eq!((res[0].lat, res[0].lng), (0.0, 0.0));
+    assert_eq!((res[1].lat, res[1].lng), (0.0, 5.0));
+    assert_eq!((res[2].lat, res[2].lng), (5.0, 5.0));
+  }
+
+  #[test]
+  fn test_simplify_climb() {
+    // straight line with a step climb in the middle
+    let mut points = vec![];
+    for i in 0..10 {
+      let alt = if i < 5 { 33000 } else { 37000 };
+      points.push(tp(0.0, i as f64 * 0.1, alt, 90, i));
+    }
+
+    let res = simplify(points, 4);
+    assert_eq!(res.len(), 4);
+    let alts: Vec<i32> = res.iter().map(|tp| tp.alt).collect();
+    assert_eq!(alts, vec![33000, 33000, 37000, 37000]);
+  }
+
+  #[test]
+  fn test_simplify_short() {
+    let points = vec![tp(0.0, 0.0, 0, 0, 0), tp(0.0, 1.0, 0, 0, 1)];
+    let res = simplify(points, 0);
+    assert_eq!(res.len(), 2);
+  }
+}
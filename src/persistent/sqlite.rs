@@ -0,0 +1,464 @@
+use super::{
+  retention_threshold, CleanupStats, ControllerSession, PendingPoint, SessionQuery, StoreError,
+  TrackInfo, TrackPoint, TrackQuery, TrackStore,
+};
+use async_trait::async_trait;
+use chrono::{Duration, TimeZone, Utc};
+use log::info;
+use rusqlite::{params, types::Value, Connection, OptionalExtension, Row};
+use std::{
+  collections::{HashMap, HashSet},
+  sync::{Arc, Mutex},
+};
+use tokio::task::spawn_blocking;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tracks (
+  id INTEGER PRIMARY KEY,
+  code TEXT NOT NULL UNIQUE,
+  created_at INTEGER NOT NULL,
+  updated_at INTEGER,
+  cid INTEGER,
+  callsign TEXT,
+  logon_time INTEGER,
+  departure TEXT,
+  arrival TEXT
+);
+CREATE TABLE IF NOT EXISTS track_points (
+  id INTEGER PRIMARY KEY,
+  track_id INTEGER NOT NULL REFERENCES tracks(id),
+  lat REAL NOT NULL,
+  lng REAL NOT NULL,
+  alt INTEGER NOT NULL,
+  hdg INTEGER NOT NULL,
+  gs INTEGER NOT NULL,
+  ts INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS controller_sessions (
+  id INTEGER PRIMARY KEY,
+  code TEXT NOT NULL UNIQUE,
+  created_at INTEGER NOT NULL,
+  cid INTEGER NOT NULL,
+  callsign TEXT NOT NULL,
+  facility TEXT NOT NULL,
+  freq INTEGER NOT NULL,
+  logon_time INTEGER NOT NULL,
+  logoff_time INTEGER,
+  atis_codes TEXT NOT NULL,
+  airport TEXT,
+  fir TEXT
+);
+";
+
+// flight details columns, missing in databases created by older versions
+const TRACK_INFO_COLUMNS: [(&str, &str); 5] = [
+  ("cid", "INTEGER"),
+  ("callsign", "TEXT"),
+  ("logon_time", "INTEGER"),
+  ("departure", "TEXT"),
+  ("arrival", "TEXT"),
+];
+
+const SELECT_TRACK_INFO: &str =
+  "SELECT id, code, cid, callsign, logon_time, departure, arrival FROM tracks";
+
+const SELECT_SESSION: &str = "SELECT code, cid, callsign, facility, freq, logon_time, logoff_time, atis_codes, airport, fir FROM controller_sessions";
+
+// atis codes are single letters so a plain string is enough to keep them
+fn read_session(row: &Row) -> Result<ControllerSession, rusqlite::Error> {
+  let logoff_time: Option<i64> = row.get(6)?;
+  let atis_codes: String = row.get(7)?;
+  Ok(ControllerSession {
+    code: row.get(0)?,
+    cid: row.get(1)?,
+    callsign: row.get(2)?,
+    facility: row.get(3)?,
+    freq: row.get(4)?,
+    logon_time: Utc.timestamp_opt(row.get(5)?, 0).unwrap(),
+    logoff_time: logoff_time.map(|ts| Utc.timestamp_opt(ts, 0).unwrap()),
+    atis_codes: atis_codes
+      .split(',')
+      .filter(|code| !code.is_empty())
+      .map(|code| code.to_owned())
+      .collect(),
+    airport: row.get(8)?,
+    fir: row.get(9)?,
+  })
+}
+
+// rusqlite connections are not Sync, all the access goes through the mutex.
+// Queries are short so it's fine to hold it while they're running. The calls
+// block so they're made on the blocking thread pool, see run().
+#[derive(Debug)]
+pub struct SqliteStore {
+  conn: Arc<Mutex<Connection>>,
+  // track ids by track code
+  track_ids: Arc<Mutex<HashMap<String, (i64, TrackInfo)>>>,
+  retention: Duration,
+}
+
+impl SqliteStore {
+  pub fn new(path: &str, retention: Duration) -> Result<Self, rusqlite::Error> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    Self::migrate(&conn)?;
+    Ok(Self {
+      conn: Arc::new(Mutex::new(conn)),
+      track_ids: Arc::new(Mutex::new(HashMap::new())),
+      retention,
+    })
+  }
+
+  // runs f with the connection locked without stalling the tokio worker
+  async fn run<F, T>(&self, f: F) -> Result<T, StoreError>
+  where
+    F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    T: Send + 'static,
+  {
+    let conn = self.conn.clone();
+    let res = spawn_blocking(move || f(&mut conn.lock().unwrap()))
+      .await
+      .expect("sqlite task panicked");
+    Ok(res?)
+  }
+
+  fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('tracks')")?;
+    let columns = stmt
+      .query_map([], |row| row.get::<_, String>(0))?
+      .collect::<Result<Vec<_>, _>>()?;
+    for (name, sql_type) in TRACK_INFO_COLUMNS {
+      if !columns.iter().any(|col| col == name) {
+        info!("adding column {name} to sqlite tracks table");
+        conn.execute(
+          &format!("ALTER TABLE tracks ADD COLUMN {name} {sql_type}"),
+          [],
+        )?;
+      }
+    }
+    // tracks used to expire by created_at leaving the recent points of
+    // long flights behind, these expire by the last point now
+    if !columns.iter().any(|col| col == "updated_at") {
+      info!("adding column updated_at to sqlite tracks table");
+      conn.execute_batch(
+        "ALTER TABLE tracks ADD COLUMN updated_at INTEGER;
+         UPDATE tracks SET updated_at = created_at;
+         DROP INDEX IF EXISTS tracks_created_at;",
+      )?;
+    }
+    Ok(())
+  }
+
+  // tracks stored by older versions have no flight details, these are
+  // returned as (id, None)
+  fn read_track(row: &Row) -> Result<(i64, Option<TrackInfo>), rusqlite::Error> {
+    let id = row.get(0)?;
+    let cid: Option<u32> = row.get(2)?;
+    let callsign: Option<String> = row.get(3)?;
+    let logon_time: Option<i64> = row.get(4)?;
+    let info = match (cid, callsign, logon_time) {
+      (Some(cid), Some(callsign), Some(logon_time)) => Some(TrackInfo {
+        code: row.get(1)?,
+        cid,
+        callsign,
+        logon_time: Utc.timestamp_opt(logon_time, 0).unwrap(),
+        departure: row.get(5)?,
+        arrival: row.get(6)?,
+      }),
+      _ => None,
+    };
+    Ok((id, info))
+  }
+
+  fn get_track(
+    conn: &Connection,
+    code: &str,
+  ) -> Result<Option<(i64, Option<TrackInfo>)>, rusqlite::Error> {
+    conn
+      .query_row(
+        &format!("{SELECT_TRACK_INFO} WHERE code = ?1"),
+        [code],
+        Self::read_track,
+      )
+      .optional()
+  }
+
+  fn get_track_id(conn: &Connection, info: &TrackInfo) -> Result<i64, rusqlite::Error> {
+    match Self::get_track(conn, &info.code)? {
+      Some((track_id, stored)) => {
+        if stored.as_ref() != Some(info) {
+          conn.execute(
+            "UPDATE tracks SET cid = ?2, callsign = ?3, logon_time = ?4, departure = ?5, arrival = ?6 WHERE id = ?1",
+            params![
+              track_id,
+              info.cid,
+              info.callsign,
+              info.logon_time.timestamp(),
+              info.departure,
+              info.arrival
+            ],
+          )?;
+        }
+        Ok(track_id)
+      }
+      None => {
+        conn.execute(
+          "INSERT INTO tracks (code, created_at, updated_at, cid, callsign, logon_time, departure, arrival) VALUES (?1, ?2, ?2, ?3, ?4, ?5, ?6, ?7)",
+          params![
+            info.code,
+            Utc::now().timestamp_millis(),
+            info.cid,
+            info.callsign,
+            info.logon_time.timestamp(),
+            info.departure,
+            info.arrival
+          ],
+        )?;
+        Ok(conn.last_insert_rowid())
+      }
+    }
+  }
+}
+
+#[async_trait]
+impl TrackStore for SqliteStore {
+  async fn indexes(&self) -> Result<(), StoreError> {
+    self
+      .run(|conn| {
+        conn.execute_batch(
+          "CREATE INDEX IF NOT EXISTS track_points_track_id_ts ON track_points (track_id, ts);
+           CREATE INDEX IF NOT EXISTS tracks_updated_at ON tracks (updated_at);
+           CREATE INDEX IF NOT EXISTS tracks_cid ON tracks (cid);
+           CREATE INDEX IF NOT EXISTS tracks_callsign ON tracks (callsign);
+           CREATE INDEX IF NOT EXISTS controller_sessions_airport ON controller_sessions (airport);
+           CREATE INDEX IF NOT EXISTS controller_sessions_fir ON controller_sessions (fir);",
+        )
+      })
+      .await
+  }
+
+  async fn cleanup(&self) -> Result<CleanupStats, StoreError> {
+    let threshold = retention_threshold(self.retention);
+    let track_ids = self.track_ids.clone();
+    self
+      .run(move |conn| {
+        let tx = conn.transaction()?;
+        let tp_count = tx.execute(
+          "DELETE FROM track_points WHERE track_id IN (SELECT id FROM tracks WHERE updated_at < ?1)",
+          [threshold.timestamp_millis()],
+        )?;
+        let count = tx.execute(
+          "DELETE FROM tracks WHERE updated_at < ?1",
+          [threshold.timestamp_millis()],
+        )?;
+        // open sessions have no logoff_time and are kept
+        let s_count = tx.execute(
+          "DELETE FROM controller_sessions WHERE logoff_time < ?1",
+          [threshold.timestamp()],
+        )?;
+        tx.commit()?;
+        // some of the cached ids might belong to the dropped tracks
+        track_ids.lock().unwrap().clear();
+        info!("{count} tracks, {tp_count} track points and {s_count} controller sessions dropped");
+        Ok(CleanupStats {
+          tracks: count as u64,
+          track_points: tp_count as u64,
+          controller_sessions: s_count as u64,
+        })
+      })
+      .await
+  }
+
+  async fn counters(&self) -> Result<(u64, u64), StoreError> {
+    self
+      .run(|conn| {
+        let t_count: u64 = conn.query_row("SELECT COUNT(*) FROM tracks", [], |row| row.get(0))?;
+        let tp_count: u64 =
+          conn.query_row("SELECT COUNT(*) FROM track_points", [], |row| row.get(0))?;
+        Ok((t_count, tp_count))
+      })
+      .await
+  }
+
+  async fn ping(&self) -> Result<(), StoreError> {
+    self
+      .run(|conn| conn.query_row("SELECT 1", [], |_| Ok(())))
+      .await
+  }
+
+  async fn store_points(&self, points: Vec<PendingPoint>) -> Result<(), StoreError> {
+    let track_ids = self.track_ids.clone();
+    self
+      .run(move |conn| {
+        let mut track_ids = track_ids.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+          let mut insert_point = tx.prepare(
+            "INSERT INTO track_points (track_id, lat, lng, alt, hdg, gs, ts) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+          )?;
+          // tracks expire by their last point so that long flights keep theirs
+          let mut touch_track = tx.prepare("UPDATE tracks SET updated_at = ?2 WHERE id = ?1")?;
+          let now = Utc::now().timestamp_millis();
+          let mut updated = HashSet::new();
+          for pp in points.into_iter() {
+            let track_id = match track_ids.get(&pp.info.code) {
+              // flight details are the same, no need to update the track
+              Some((track_id, info)) if info == &pp.info => *track_id,
+              _ => {
+                let track_id = Self::get_track_id(&tx, &pp.info)?;
+                track_ids.insert(pp.info.code.clone(), (track_id, pp.info));
+                track_id
+              }
+            };
+
+            let tp = pp.point;
+            insert_point.execute(params![
+              track_id, tp.lat, tp.lng, tp.alt, tp.hdg, tp.gs, tp.ts
+            ])?;
+            if updated.insert(track_id) {
+              touch_track.execute(params![track_id, now])?;
+            }
+          }
+        }
+        tx.commit()
+      })
+      .await
+  }
+
+  async fn get_track_points(
+    &self,
+    code: &str,
+    since: Option<i64>,
+  ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
+    let code = code.to_owned();
+    self
+      .run(move |conn| {
+        let track = Self::get_track(conn, &code)?;
+        if let Some((track_id, _)) = track {
+          let mut stmt = conn.prepare(
+            "SELECT lat, lng, alt, hdg, gs, ts FROM track_points WHERE track_id = ?1 AND ts > ?2 ORDER BY ts, id",
+          )?;
+          let rows = stmt.query_map(params![track_id, since.unwrap_or(i64::MIN)], |row| {
+            Ok(TrackPoint {
+              lat: row.get(0)?,
+              lng: row.get(1)?,
+              alt: row.get(2)?,
+              hdg: row.get(3)?,
+              gs: row.get(4)?,
+              ts: row.get(5)?,
+            })
+          })?;
+          let mut tps = vec![];
+          for tp in rows {
+            tps.push(tp?);
+          }
+          Ok(Some(tps))
+        } else {
+          Ok(None)
+        }
+      })
+      .await
+  }
+
+  async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError> {
+    let code = code.to_owned();
+    self
+      .run(move |conn| {
+        let track = Self::get_track(conn, &code)?;
+        Ok(track.and_then(|(_, info)| info))
+      })
+      .await
+  }
+
+  async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError> {
+    let (cond, value) = match query {
+      TrackQuery::Cid(cid) => ("cid", Value::Integer(*cid as i64)),
+      TrackQuery::Callsign(callsign) => ("callsign", Value::Text(callsign.clone())),
+    };
+    self
+      .run(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+          "{SELECT_TRACK_INFO} WHERE {cond} = ?1 ORDER BY logon_time DESC"
+        ))?;
+        let rows = stmt.query_map([value], Self::read_track)?;
+        let mut res = vec![];
+        for row in rows {
+          if let (_, Some(info)) = row? {
+            res.push(info);
+          }
+        }
+        Ok(res)
+      })
+      .await
+  }
+
+  async fn store_controller_sessions(
+    &self,
+    sessions: Vec<ControllerSession>,
+  ) -> Result<(), StoreError> {
+    self
+      .run(move |conn| {
+        let tx = conn.transaction()?;
+        {
+          let mut select = tx.prepare(&format!("{SELECT_SESSION} WHERE code = ?1"))?;
+          let mut upsert = tx.prepare(
+            "INSERT INTO controller_sessions (code, created_at, cid, callsign, facility, freq, logon_time, logoff_time, atis_codes, airport, fir)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(code) DO UPDATE SET
+               facility = excluded.facility,
+               freq = excluded.freq,
+               logoff_time = excluded.logoff_time,
+               atis_codes = excluded.atis_codes,
+               airport = excluded.airport,
+               fir = excluded.fir",
+          )?;
+          let now = Utc::now().timestamp_millis();
+          for session in sessions.into_iter() {
+            let stored = select.query_row([&session.code], read_session).optional()?;
+            let atis_codes = match stored {
+              Some(mut stored) => {
+                stored.merge_atis_codes(&session.atis_codes);
+                stored.atis_codes
+              }
+              None => session.atis_codes,
+            };
+            upsert.execute(params![
+              session.code,
+              now,
+              session.cid,
+              session.callsign,
+              session.facility,
+              session.freq,
+              session.logon_time.timestamp(),
+              session.logoff_time.map(|t| t.timestamp()),
+              atis_codes.join(","),
+              session.airport,
+              session.fir
+            ])?;
+          }
+        }
+        tx.commit()
+      })
+      .await
+  }
+
+  async fn find_controller_sessions(
+    &self,
+    query: &SessionQuery,
+  ) -> Result<Vec<ControllerSession>, StoreError> {
+    let query = query.clone();
+    self
+      .run(move |conn| {
+        let mut stmt = conn.prepare(&format!(
+          "{SELECT_SESSION} WHERE (?1 IS NULL OR airport = ?1) AND (?2 IS NULL OR fir = ?2) ORDER BY logon_time DESC, id"
+        ))?;
+        let rows = stmt.query_map(params![query.airport, query.fir], read_session)?;
+        let mut res = vec![];
+        for session in rows {
+          res.push(session?);
+        }
+        Ok(res)
+      })
+      .await
+  }
+}
@@ -0,0 +1,341 @@
+use super::{detect::ChangeDetector, PendingPoint, TrackStore};
+use crate::{moving::pilot::Pilot, seconds_since};
+use chrono::Utc;
+use log::{debug, error};
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Arc,
+};
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+
+// vatsim has ~1500 pilots online at peak times so a single poll
+// normally fits into a couple of batches
+const MAX_BATCH_SIZE: usize = 1000;
+// points queued while the store is slow or down, a few polls worth
+const MAX_QUEUE_SIZE: usize = 10000;
+// consecutive failed attempts to store the same points before giving up
+const MAX_RETRIES: usize = 5;
+
+/// TrackWriter persists track points in the background so the processing
+/// loop never waits for the database. Points are sent over a channel and
+/// flushed in batches containing everything accumulated since the previous flush.
+/// Points which don't differ enough from the previous ones are skipped,
+/// see ChangeDetector. If the queue is full the store can't keep up and
+/// new points are dropped. Points which couldn't be stored are retried
+/// along with the next batch up to MAX_RETRIES times.
+#[derive(Debug, Clone)]
+pub struct TrackWriter {
+  tx: Sender<PendingPoint>,
+  skipped: Arc<AtomicU64>,
+  dropped: Arc<AtomicU64>,
+}
+
+impl TrackWriter {
+  pub fn spawn(store: Arc<dyn TrackStore>, detector: ChangeDetector) -> Self {
+    Self::with_queue_size(store, detector, MAX_QUEUE_SIZE)
+  }
+
+  fn with_queue_size(
+    store: Arc<dyn TrackStore>,
+    detector: ChangeDetector,
+    queue_size: usize,
+  ) -> Self {
+    let (tx, rx) = channel(queue_size);
+    let skipped = Arc::new(AtomicU64::new(0));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let counters = (skipped.clone(), dropped.clone());
+    tokio::spawn(async move { run(store, detector, rx, queue_size, counters).await });
+    Self {
+      tx,
+      skipped,
+      dropped,
+    }
+  }
+
+  pub fn push(&self, pilot: &Pilot) {
+    match self.tx.try_send(pilot.into()) {
+      Ok(_) => (),
+      Err(TrySendError::Full(_)) => {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+      }
+      Err(TrySendError::Closed(_)) => {
+        error!("track writer is gone, dropping track point");
+      }
+    }
+  }
+
+  /// Total number of points dropped as the queue was full or the
+  /// store kept failing
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  /// Total number of points skipped by change detection
+  pub fn skipped(&self) -> u64 {
+    self.skipped.load(Ordering::Relaxed)
+  }
+}
+
+// drops the oldest points above the limit, returns the number of points dropped
+fn drop_oldest(
+  points: &mut Vec<PendingPoint>,
+  limit: usize,
+  detector: &mut ChangeDetector,
+) -> usize {
+  let excess = points.len().saturating_sub(limit);
+  let dropped: Vec<PendingPoint> = points.drain(..excess).collect();
+  detector.forget(dropped.iter().map(|pp| pp.info.code.as_str()));
+  excess
+}
+
+async fn run(
+  store: Arc<dyn TrackStore>,
+  mut detector: ChangeDetector,
+  mut rx: Receiver<PendingPoint>,
+  queue_size: usize,
+  (skipped, dropped): (Arc<AtomicU64>, Arc<AtomicU64>),
+) {
+  // points which couldn't be stored, these go first with the next batch
+  let mut failed: Vec<PendingPoint> = vec![];
+  let mut attempts = 0;
+  while let Some(point) = rx.recv().await {
+    let mut received = vec![point];
+    while received.len() < MAX_BATCH_SIZE {
+      match rx.try_recv() {
+        Ok(point) => received.push(point),
+        Err(_) => break,
+      }
+    }
+
+    let total = received.len();
+    // point times come from the feed, they're behind the wall clock in replay
+    let latest_ts = received
+      .iter()
+      .map(|pp| pp.point.ts)
+      .max()
+      .unwrap_or_default();
+    let accepted: Vec<PendingPoint> = received
+      .into_iter()
+      .filter(|pp| detector.check(pp))
+      .collect();
+    let accepted_count = accepted.len();
+    skipped.fetch_add((total - accepted_count) as u64, Ordering::Relaxed);
+    detector.purge(latest_ts);
+
+    let mut batch = std::mem::take(&mut failed);
+    batch.extend(accepted);
+    if batch.is_empty() {
+      continue;
+    }
+
+    let t = Utc::now();
+    let count = batch.len();
+    let res = store.store_points(batch.clone()).await;
+    match res {
+      Ok(_) => {
+        attempts = 0;
+        debug!(
+          "{count} track points stored, {} skipped in {}s",
+          total - accepted_count,
+          seconds_since(t)
+        )
+      }
+      Err(err) => {
+        attempts += 1;
+        // the detector has taken the points for stored ones, it has to
+        // forget the tracks of the dropped points
+        let limit = if attempts > MAX_RETRIES {
+          0
+        } else {
+          queue_size
+        };
+        let gone = drop_oldest(&mut batch, limit, &mut detector);
+        dropped.fetch_add(gone as u64, Ordering::Relaxed);
+        if batch.is_empty() {
+          attempts = 0;
+          error!("error storing {count} track points, {gone} dropped: {err}");
+        } else {
+          error!("error storing {count} track points, {gone} dropped, retrying: {err}");
+        }
+        failed = batch;
+      }
+    }
+  }
+  debug!("track writer channel closed, exiting");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::Track as TrackConfig,
+    persistent::{
+      memory::MemoryStore, tests::test_pilot, CleanupStats, ControllerSession, SessionQuery,
+      StoreError, TrackInfo, TrackPoint, TrackQuery,
+    },
+  };
+  use async_trait::async_trait;
+  use chrono::Duration;
+  use tokio::time::{sleep, Duration as TDuration};
+
+  #[tokio::test]
+  async fn test_writer() {
+    let store = Arc::new(MemoryStore::new(Duration::days(2)));
+    let detector = ChangeDetector::new(&TrackConfig::default());
+    let writer = TrackWriter::spawn(store.clone(), detector);
+    let mut pilot = test_pilot("BAW1");
+    writer.push(&pilot);
+    writer.push(&test_pilot("BAW2"));
+    writer.push(&test_pilot("BAW3"));
+    // the pilot hasn't moved, this one is skipped
+    writer.push(&pilot);
+    pilot.altitude = 3000;
+    writer.push(&pilot);
+
+    let mut counters = (0, 0);
+    for _ in 0..50 {
+      counters = store.counters().await.unwrap();
+      if counters.1 == 4 {
+        break;
+      }
+      sleep(TDuration::from_millis(10)).await;
+    }
+    assert_eq!(counters, (3, 4));
+    assert_eq!(writer.skipped(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_writer_queue_full() {
+    let store = Arc::new(MemoryStore::new(Duration::days(2)));
+    let detector = ChangeDetector::new(&TrackConfig::default());
+    let writer = TrackWriter::with_queue_size(store, detector, 2);
+    // the writer task doesn't get to run until the test yields
+    writer.push(&test_pilot("BAW1"));
+    writer.push(&test_pilot("BAW2"));
+    writer.push(&test_pilot("BAW3"));
+    writer.push(&test_pilot("BAW4"));
+    assert_eq!(writer.dropped(), 2);
+  }
+
+  // fails to store points the given number of times
+  #[derive(Debug)]
+  struct FlakyStore {
+    inner: MemoryStore,
+    failures: AtomicU64,
+    attempts: AtomicU64,
+  }
+
+  impl FlakyStore {
+    fn new(failures: u64) -> Self {
+      Self {
+        inner: MemoryStore::new(Duration::days(2)),
+        failures: AtomicU64::new(failures),
+        attempts: AtomicU64::new(0),
+      }
+    }
+
+    async fn wait_attempts(&self, attempts: u64) {
+      wait_until(|| self.attempts.load(Ordering::Relaxed) >= attempts).await;
+    }
+  }
+
+  async fn wait_until<F: Fn() -> bool>(cond: F) {
+    for _ in 0..50 {
+      if cond() {
+        return;
+      }
+      sleep(TDuration::from_millis(10)).await;
+    }
+    panic!("the writer hasn't got there in time");
+  }
+
+  #[async_trait]
+  impl TrackStore for FlakyStore {
+    async fn indexes(&self) -> Result<(), StoreError> {
+      self.inner.indexes().await
+    }
+    async fn cleanup(&self) -> Result<CleanupStats, StoreError> {
+      self.inner.cleanup().await
+    }
+    async fn counters(&self) -> Result<(u64, u64), StoreError> {
+      self.inner.counters().await
+    }
+    async fn ping(&self) -> Result<(), StoreError> {
+      self.inner.ping().await
+    }
+    async fn store_points(&self, points: Vec<PendingPoint>) -> Result<(), StoreError> {
+      let failures = self.failures.load(Ordering::Relaxed);
+      let res = if failures > 0 {
+        self.failures.store(failures - 1, Ordering::Relaxed);
+        Err(StoreError::Sqlite(rusqlite::Error::InvalidQuery))
+      } else {
+        self.inner.store_points(points).await
+      };
+      self.attempts.fetch_add(1, Ordering::Relaxed);
+      res
+    }
+    async fn get_track_points(
+      &self,
+      code: &str,
+      since: Option<i64>,
+    ) -> Result<Option<Vec<TrackPoint>>, StoreError> {
+      self.inner.get_track_points(code, since).await
+    }
+    async fn get_track_info(&self, code: &str) -> Result<Option<TrackInfo>, StoreError> {
+      self.inner.get_track_info(code).await
+    }
+    async fn find_tracks(&self, query: &TrackQuery) -> Result<Vec<TrackInfo>, StoreError> {
+      self.inner.find_tracks(query).await
+    }
+    async fn store_controller_sessions(
+      &self,
+      sessions: Vec<ControllerSession>,
+    ) -> Result<(), StoreError> {
+      self.inner.store_controller_sessions(sessions).await
+    }
+    async fn find_controller_sessions(
+      &self,
+      query: &SessionQuery,
+    ) -> Result<Vec<ControllerSession>, StoreError> {
+      self.inner.find_controller_sessions(query).await
+    }
+  }
+
+  #[tokio::test]
+  async fn test_writer_retry() {
+    let store = Arc::new(FlakyStore::new(1));
+    let detector = ChangeDetector::new(&TrackConfig::default());
+    let writer = TrackWriter::spawn(store.clone(), detector);
+    writer.push(&test_pilot("BAW1"));
+    writer.push(&test_pilot("BAW2"));
+    store.wait_attempts(1).await;
+
+    // the failed points are stored along with the next ones
+    writer.push(&test_pilot("BAW3"));
+    store.wait_attempts(2).await;
+    assert_eq!(store.counters().await.unwrap(), (3, 3));
+    assert_eq!(writer.dropped(), 0);
+  }
+
+  #[tokio::test]
+  async fn test_writer_retry_limit() {
+    let store = Arc::new(FlakyStore::new(u64::MAX));
+    let detector = ChangeDetector::new(&TrackConfig::default());
+    let writer = TrackWriter::with_queue_size(store.clone(), detector, 2);
+    writer.push(&test_pilot("BAW1"));
+    writer.push(&test_pilot("BAW2"));
+    store.wait_attempts(1).await;
+
+    // failed points count against the queue size
+    writer.push(&test_pilot("BAW3"));
+    wait_until(|| writer.dropped() == 1).await;
+
+    // the forgotten BAW1 is accepted again and pushes BAW2 out, the
+    // remaining two go once the store has failed too many times
+    for attempt in 3..=MAX_RETRIES as u64 + 1 {
+      writer.push(&test_pilot("BAW1"));
+      store.wait_attempts(attempt).await;
+    }
+    wait_until(|| writer.dropped() == 4).await;
+  }
+}
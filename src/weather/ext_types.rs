@@ -1,7 +1,7 @@
 use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
 
-const WHY_IS_IT_EVEN_CUSTOM_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+const WHY_IS_IT_EVEN_CUSTOM_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
 pub fn deserialize_datetime<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
@@ -68,12 +68,30 @@ impl BlackListItem {
   }
 }
 
+#[derive(Debug, Default)]
+struct ProviderState {
+  last_success: Option<DateTime<Utc>>,
+  last_error: Option<(DateTime<Utc>, String)>,
+}
+
+/// Weather provider state, the provider is considered healthy
+/// unless the latest request to it has failed
+#[derive(Debug, Clone, Serialize)]
+pub struct WeatherStatus {
+  pub healthy: bool,
+  pub requests: usize,
+  pub cached_locations: usize,
+  pub last_success: Option<DateTime<Utc>>,
+  pub last_error: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct WeatherManager {
   metar_ttl: Duration,
   cache: RwLock<HashMap<String, WeatherInfo>>,
   blacklist: RwLock<HashMap<String, BlackListItem>>,
   apireq_num: AtomicUsize,
+  state: RwLock<ProviderState>,
 }
 
 impl WeatherManager {
@@ -83,9 +101,34 @@ impl WeatherManager {
       cache: Default::default(),
       blacklist: Default::default(),
       apireq_num: AtomicUsize::new(0),
+      state: Default::default(),
     }
   }
 
+  pub async fn status(&self) -> WeatherStatus {
+    let state = self.state.read().await;
+    let healthy = match (&state.last_error, state.last_success) {
+      (None, _) => true,
+      (Some((err_at, _)), Some(success_at)) => success_at > *err_at,
+      (Some(_), None) => false,
+    };
+    WeatherStatus {
+      healthy,
+      requests: self.request_num(),
+      cached_locations: self.cache.read().await.len(),
+      last_success: state.last_success,
+      last_error: state.last_error.as_ref().map(|(_, err)| err.clone()),
+    }
+  }
+
+  async fn set_success(&self) {
+    self.state.write().await.last_success = Some(Utc::now());
+  }
+
+  async fn set_error(&self, err: String) {
+    self.state.write().await.last_error = Some((Utc::now(), err));
+  }
+
   pub fn request_num(&self) -> usize {
     self.apireq_num.load(Ordering::Relaxed)
   }
@@ -176,15 +219,18 @@ impl WeatherManager {
 
     if let Err(err) = res {
       error!("error loading wx data: {err}");
+      self.set_error(err.to_string()).await;
       return;
     }
 
     let res = res.unwrap().json::<Vec<Metar>>().await;
     if let Err(err) = res {
       error!("error parsing wx data: {err}");
+      self.set_error(err.to_string()).await;
       return;
     }
 
+    self.set_success().await;
     let metars = res.unwrap();
     let mut cache = self.cache.write().await;
     for metar in metars {
@@ -226,16 +272,20 @@ impl WeatherManager {
 
     if let Err(err) = res {
       error!("error loading {location} wx data: {err}");
+      self.set_error(err.to_string()).await;
       return None;
     }
 
     let metar = res.unwrap().json::<Vec<Metar>>().await;
     if let Err(err) = metar {
       error!("error parsing {location} wx data: {err}");
+      self.set_error(err.to_string()).await;
       return None;
     }
 
-    let metar = metar.unwrap().get(0).cloned();
+    self.set_success().await;
+
+    let metar = metar.unwrap().first().cloned();
     if let Some(metar) = metar {
       Some(metar.into())
     } else {
@@ -0,0 +1,158 @@
+use super::error::{api_error, APIError};
+use crate::{
+  fixed::validate::ValidationReport,
+  manager::{FixedDataStats, Manager, UnmatchedController},
+  moving::{
+    replay::{ReplaySource, ReplayStatus},
+    source::FeedError,
+  },
+};
+use chrono::{DateTime, Utc};
+use rocket::{
+  get,
+  http::Status,
+  post,
+  request::{FromRequest, Outcome},
+  serde::json::Json,
+  Request, State,
+};
+use std::sync::Arc;
+
+/// Admin is a request guard for the admin endpoints. Requests must carry
+/// the configured admin token as "Authorization: Bearer <token>", without
+/// a token configured the admin endpoints are disabled altogether
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+  type Error = ();
+
+  async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+    let manager = req.rocket().state::<Arc<Manager>>();
+    let token = manager.and_then(|m| m.config().web.admin_token.as_ref());
+    match token {
+      None => Outcome::Failure((Status::Forbidden, ())),
+      Some(token) => {
+        let auth = req.headers().get_one("Authorization");
+        let expected = format!("Bearer {token}");
+        if auth == Some(expected.as_str()) {
+          Outcome::Success(Admin)
+        } else {
+          Outcome::Failure((Status::Unauthorized, ()))
+        }
+      }
+    }
+  }
+}
+
+impl From<FeedError> for APIError {
+  fn from(err: FeedError) -> Self {
+    let code = match err {
+      FeedError::NotReplaying => 404,
+      FeedError::Replay(_) => 400,
+      _ => 500,
+    };
+    api_error(code, &err.to_string())
+  }
+}
+
+fn replay(manager: &Manager) -> Result<&ReplaySource, APIError> {
+  manager
+    .replay()
+    .ok_or_else(|| FeedError::NotReplaying.into())
+}
+
+#[get("/admin/replay")]
+pub async fn replay_status(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let status = replay(manager)?.status().await?;
+  Ok(Json(status))
+}
+
+#[post("/admin/replay/pause")]
+pub async fn replay_pause(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let replay = replay(manager)?;
+  replay.pause().await;
+  Ok(Json(replay.status().await?))
+}
+
+#[post("/admin/replay/resume")]
+pub async fn replay_resume(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let replay = replay(manager)?;
+  replay.resume().await;
+  Ok(Json(replay.status().await?))
+}
+
+#[post("/admin/replay/step")]
+pub async fn replay_step(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let replay = replay(manager)?;
+  replay.step().await;
+  Ok(Json(replay.status().await?))
+}
+
+#[post("/admin/replay/speed/<speed>")]
+pub async fn replay_speed(
+  speed: f64,
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let replay = replay(manager)?;
+  replay.set_speed(speed).await?;
+  Ok(Json(replay.status().await?))
+}
+
+// ts is RFC 3339, i.e. 2022-12-17T18:00:00Z
+#[post("/admin/replay/seek?<ts>")]
+pub async fn replay_seek(
+  ts: String,
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<ReplayStatus>, APIError> {
+  let ts = DateTime::parse_from_rfc3339(&ts)
+    .map_err(|err| api_error(400, &format!("invalid timestamp: {err}")))?
+    .with_timezone(&Utc);
+  let replay = replay(manager)?;
+  replay.seek(ts).await?;
+  Ok(Json(replay.status().await?))
+}
+
+#[post("/admin/fixed/reload")]
+pub async fn fixed_reload(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<FixedDataStats>, APIError> {
+  let stats = manager
+    .reload_fixed_data()
+    .await
+    .map_err(|err| api_error(500, &format!("error reloading fixed data: {err}")))?;
+  Ok(Json(stats))
+}
+
+#[get("/admin/fixed/validation")]
+pub async fn fixed_validation(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Json<ValidationReport> {
+  Json(manager.fixed_validation().await)
+}
+
+// online controllers which couldn't be attached to any position,
+// candidates for callsign rules
+#[get("/admin/controllers/unmatched")]
+pub async fn unmatched_controllers(
+  _admin: Admin,
+  manager: &State<Arc<Manager>>,
+) -> Json<Vec<UnmatchedController>> {
+  Json(manager.get_unmatched_controllers().await)
+}
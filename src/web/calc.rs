@@ -1,5 +1,5 @@
 use crate::{
-  fixed::types::{Airport, FIR},
+  fixed::types::{Airport, FIR, TRACON, UIR},
   moving::pilot::Pilot,
 };
 use std::collections::{HashMap, HashSet};
@@ -95,3 +95,157 @@ pub fn calc_firs(firs: &[FIR], prev: &mut HashMap<String, FIR>) -> (Vec<FIR>, Ve
 
   (firs_set, firs_delete)
 }
+
+pub fn calc_uirs(uirs: &[UIR], prev: &mut HashMap<String, UIR>) -> (Vec<UIR>, Vec<UIR>) {
+  let mut uirs_set = vec![];
+  let mut uirs_delete = vec![];
+  let mut keys = HashSet::new();
+
+  for uir in uirs.iter() {
+    let existing = prev.get(&uir.icao);
+    keys.insert(uir.icao.clone());
+    if let Some(existing) = existing {
+      if existing == uir {
+        continue;
+      }
+    }
+    uirs_set.push(uir.clone());
+    prev.insert(uir.icao.clone(), uir.clone());
+  }
+
+  let prev_keys = HashSet::from_iter(prev.keys().cloned());
+  let keys_to_remove = prev_keys.difference(&keys);
+  for key in keys_to_remove {
+    let uir = prev.remove(key).unwrap();
+    uirs_delete.push(uir);
+  }
+
+  (uirs_set, uirs_delete)
+}
+
+pub fn calc_tracons(
+  tracons: &[TRACON],
+  prev: &mut HashMap<String, TRACON>,
+) -> (Vec<TRACON>, Vec<TRACON>) {
+  let mut tracons_set = vec![];
+  let mut tracons_delete = vec![];
+  let mut keys = HashSet::new();
+
+  for tracon in tracons.iter() {
+    let key = tracon.key();
+    let existing = prev.get(&key);
+    keys.insert(key.clone());
+    if let Some(existing) = existing {
+      if existing == tracon {
+        continue;
+      }
+    }
+    tracons_set.push(tracon.clone());
+    prev.insert(key, tracon.clone());
+  }
+
+  let prev_keys = HashSet::from_iter(prev.keys().cloned());
+  let keys_to_remove = prev_keys.difference(&keys);
+  for key in keys_to_remove {
+    let tracon = prev.remove(key).unwrap();
+    tracons_delete.push(tracon);
+  }
+
+  (tracons_set, tracons_delete)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    fixed::types::Boundaries,
+    moving::controller::{ControllerSet, Facility},
+    persistent::tests::test_controller,
+    types::Point,
+  };
+
+  #[test]
+  fn test_calc_airports() {
+    let mut arpt = Airport {
+      icao: "EDDF".into(),
+      iata: "FRA".into(),
+      name: "Frankfurt".into(),
+      position: Point {
+        lat: 50.03,
+        lng: 8.57,
+      },
+      fir_id: "EDGG".into(),
+      is_pseudo: false,
+      controllers: ControllerSet::empty(),
+      runways: HashMap::new(),
+      country: None,
+      wx: None,
+    };
+    let north = test_controller("EDDF_N_TWR", Facility::Tower);
+    let south = test_controller("EDDF_S_TWR", Facility::Tower);
+    let mut state = HashMap::new();
+
+    arpt.controllers.insert(north.clone());
+    let (set, delete) = calc_airports(&[arpt.clone()], &mut state);
+    assert_eq!((set.len(), delete.len()), (1, 0));
+    let (set, _) = calc_airports(&[arpt.clone()], &mut state);
+    assert!(set.is_empty());
+
+    // the second tower changes the airport
+    arpt.controllers.insert(south.clone());
+    let (set, _) = calc_airports(&[arpt.clone()], &mut state);
+    assert_eq!(set.len(), 1);
+    assert_eq!(set[0].controllers.tower.len(), 2);
+
+    // one of them logs off, the other one stays
+    arpt.controllers.remove(&north);
+    let (set, delete) = calc_airports(&[arpt.clone()], &mut state);
+    assert_eq!((set.len(), delete.len()), (1, 0));
+    assert!(set[0].controllers.tower.contains_key("EDDF_S_TWR"));
+
+    arpt.controllers.remove(&south);
+    let (set, delete) = calc_airports(&[], &mut state);
+    assert_eq!((set.len(), delete.len()), (0, 1));
+  }
+
+  #[test]
+  fn test_calc_firs_reloaded() {
+    let p = |lat: f64, lng: f64| Point { lat, lng };
+    let mut fir = FIR {
+      icao: "EGTT".into(),
+      name: "London".into(),
+      prefix: "".into(),
+      boundaries: Boundaries {
+        id: "EGTT".into(),
+        region: "EMEA".into(),
+        division: "GBR".into(),
+        is_oceanic: false,
+        min: p(50.0, -2.0),
+        max: p(53.0, 2.0),
+        center: p(51.5, 0.0),
+        points: vec![vec![
+          p(50.0, -2.0),
+          p(53.0, -2.0),
+          p(53.0, 2.0),
+          p(50.0, -2.0),
+        ]],
+      },
+      controllers: HashMap::new(),
+      country: None,
+    };
+    fir.controllers.insert(
+      "LON_CTR".into(),
+      test_controller("LON_CTR", Facility::Radar),
+    );
+    let mut state = HashMap::new();
+    let (set, _) = calc_firs(&[fir.clone()], &mut state);
+    assert_eq!(set.len(), 1);
+    let (set, _) = calc_firs(&[fir.clone()], &mut state);
+    assert!(set.is_empty());
+
+    // new boundaries after a fixed data reload are pushed to clients
+    fir.boundaries.points[0][1] = p(54.0, -2.0);
+    let (set, _) = calc_firs(&[fir.clone()], &mut state);
+    assert_eq!(set.len(), 1);
+  }
+}
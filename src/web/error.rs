@@ -5,7 +5,10 @@ use rocket::{
   serde::json::json,
 };
 
-use crate::lee::parser::error::{CompileError, ParseError};
+use crate::{
+  lee::parser::error::{CompileError, ParseError},
+  persistent::StoreError,
+};
 
 #[derive(Debug)]
 pub struct APIError {
@@ -43,8 +46,8 @@ impl From<CompileError> for APIError {
   }
 }
 
-impl From<mongodb::error::Error> for APIError {
-  fn from(err: mongodb::error::Error) -> Self {
+impl From<StoreError> for APIError {
+  fn from(err: StoreError) -> Self {
     APIError {
       code: 500,
       message: format!("{}", err),
@@ -67,6 +70,16 @@ pub fn bad_request(message: &str) -> APIError {
   api_error(403, message)
 }
 
+#[catch(401)]
+pub fn catch401() -> APIError {
+  api_error(401, "unauthorized")
+}
+
+#[catch(403)]
+pub fn catch403() -> APIError {
+  api_error(403, "forbidden")
+}
+
 #[catch(404)]
 pub fn catch404() -> APIError {
   not_found("not found")
@@ -1,10 +1,10 @@
 use crate::{
   lee::parser::{
-    condition::{Condition, Value},
+    condition::{Condition, Operator, Value},
     error::CompileError,
     expression::EvaluateFunc,
   },
-  moving::pilot::Pilot,
+  moving::pilot::{FlightPhase, Pilot},
 };
 use lazy_static::lazy_static;
 
@@ -20,6 +20,13 @@ lazy_static! {
     "arrival",
     "departure",
     "rules",
+    "fir",
+    "controlled_by",
+    "phase",
+    "dist_flown",
+    "dist_remaining",
+    "progress",
+    "eta_min",
   ];
 }
 
@@ -60,6 +67,65 @@ pub fn compile_filter(cond: Condition) -> Result<Box<EvaluateFunc<Pilot>>, Compi
           .unwrap_or(false)
       })
     }
+    "phase" => {
+      // regex operators are matched against the phase name as is
+      if let Value::String(v) = &value {
+        let known = FlightPhase::ALL
+          .iter()
+          .any(|phase| phase.to_string() == v.as_str());
+        if !known && !v.is_empty() && matches!(operator, Operator::Equals | Operator::NotEquals) {
+          return Err(CompileError {
+            msg: format!(
+              "invalid phase value, valid ones are [{}]",
+              FlightPhase::ALL
+                .iter()
+                .map(|phase| format!("'{phase}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+            ),
+          });
+        }
+      } else {
+        return Err(CompileError {
+          msg: format!("invalid phase value type {}", value.value_type()),
+        });
+      }
+      Box::new(move |pilot| {
+        pilot
+          .phase
+          .map(|phase| value.eval_str(&phase.to_string(), operator.clone()))
+          .unwrap_or(false)
+      })
+    }
+    "dist_flown" => Box::new(move |pilot| {
+      pilot
+        .progress
+        .as_ref()
+        .map(|p| value.eval_f64(p.dist_flown_nm, operator.clone()))
+        .unwrap_or(false)
+    }),
+    "dist_remaining" => Box::new(move |pilot| {
+      pilot
+        .progress
+        .as_ref()
+        .map(|p| value.eval_f64(p.dist_remaining_nm, operator.clone()))
+        .unwrap_or(false)
+    }),
+    "progress" => Box::new(move |pilot| {
+      pilot
+        .progress
+        .as_ref()
+        .map(|p| value.eval_f64(p.percent, operator.clone()))
+        .unwrap_or(false)
+    }),
+    "eta_min" => Box::new(move |pilot| {
+      pilot
+        .progress
+        .as_ref()
+        .and_then(|p| p.eta_min)
+        .map(|eta_min| value.eval_i64(eta_min, operator.clone()))
+        .unwrap_or(false)
+    }),
     "callsign" => Box::new(move |pilot| value.eval_str(&pilot.callsign, operator.clone())),
     "name" => Box::new(move |pilot| value.eval_str(&pilot.name, operator.clone())),
     "alt" => Box::new(move |pilot| value.eval_i64(pilot.altitude as i64, operator.clone())),
@@ -87,6 +153,20 @@ pub fn compile_filter(cond: Condition) -> Result<Box<EvaluateFunc<Pilot>>, Compi
         .map(|fp| value.eval_str(&fp.departure, operator.clone()))
         .unwrap_or(false)
     }),
+    "fir" => Box::new(move |pilot| {
+      pilot
+        .fir
+        .as_ref()
+        .map(|fir| value.eval_str(fir, operator.clone()))
+        .unwrap_or(false)
+    }),
+    "controlled_by" => Box::new(move |pilot| {
+      pilot
+        .controlled_by
+        .as_ref()
+        .map(|cs| value.eval_str(cs, operator.clone()))
+        .unwrap_or(false)
+    }),
     _ => {
       return Err(CompileError {
         msg: format!(
@@ -1,7 +1,7 @@
 use serde::Serialize;
 
 use crate::{
-  fixed::types::{Airport, FIR},
+  fixed::types::{Airport, FIR, TRACON, UIR},
   moving::pilot::Pilot,
 };
 
@@ -13,6 +13,10 @@ pub struct ObjectsSet {
   pub airports: Option<Vec<Airport>>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub firs: Option<Vec<FIR>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub uirs: Option<Vec<UIR>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tracons: Option<Vec<TRACON>>,
 }
 
 impl ObjectsSet {
@@ -21,6 +25,8 @@ impl ObjectsSet {
     self.pilots.as_ref().map(|p| p.is_empty()).unwrap_or(true)
       && self.airports.as_ref().map(|a| a.is_empty()).unwrap_or(true)
       && self.firs.as_ref().map(|f| f.is_empty()).unwrap_or(true)
+      && self.uirs.as_ref().map(|u| u.is_empty()).unwrap_or(true)
+      && self.tracons.as_ref().map(|t| t.is_empty()).unwrap_or(true)
   }
 }
 
@@ -56,6 +62,8 @@ impl UpdateMessage {
           pilots: Some(data),
           airports: None,
           firs: None,
+          uirs: None,
+          tracons: None,
         }),
         delete: None,
       },
@@ -72,6 +80,8 @@ impl UpdateMessage {
           pilots: Some(data),
           airports: None,
           firs: None,
+          uirs: None,
+          tracons: None,
         }),
       },
     }
@@ -86,6 +96,8 @@ impl UpdateMessage {
           pilots: None,
           airports: Some(data),
           firs: None,
+          uirs: None,
+          tracons: None,
         }),
         delete: None,
       },
@@ -102,6 +114,8 @@ impl UpdateMessage {
           pilots: None,
           airports: Some(data),
           firs: None,
+          uirs: None,
+          tracons: None,
         }),
       },
     }
@@ -116,6 +130,8 @@ impl UpdateMessage {
           pilots: None,
           airports: None,
           firs: Some(data),
+          uirs: None,
+          tracons: None,
         }),
         delete: None,
       },
@@ -132,6 +148,76 @@ impl UpdateMessage {
           pilots: None,
           airports: None,
           firs: Some(data),
+          uirs: None,
+          tracons: None,
+        }),
+      },
+    }
+  }
+  pub fn tracons_set(connection_id: &str, data: Vec<TRACON>) -> Self {
+    Self {
+      connection_id: connection_id.to_owned(),
+      message_type: "update",
+      object_type: "tracon",
+      data: Update {
+        set: Some(ObjectsSet {
+          pilots: None,
+          airports: None,
+          firs: None,
+          uirs: None,
+          tracons: Some(data),
+        }),
+        delete: None,
+      },
+    }
+  }
+  pub fn tracons_delete(connection_id: &str, data: Vec<TRACON>) -> Self {
+    Self {
+      connection_id: connection_id.to_owned(),
+      message_type: "update",
+      object_type: "tracon",
+      data: Update {
+        set: None,
+        delete: Some(ObjectsSet {
+          pilots: None,
+          airports: None,
+          firs: None,
+          uirs: None,
+          tracons: Some(data),
+        }),
+      },
+    }
+  }
+  pub fn uirs_set(connection_id: &str, data: Vec<UIR>) -> Self {
+    Self {
+      connection_id: connection_id.to_owned(),
+      message_type: "update",
+      object_type: "uir",
+      data: Update {
+        set: Some(ObjectsSet {
+          pilots: None,
+          airports: None,
+          firs: None,
+          uirs: Some(data),
+          tracons: None,
+        }),
+        delete: None,
+      },
+    }
+  }
+  pub fn uirs_delete(connection_id: &str, data: Vec<UIR>) -> Self {
+    Self {
+      connection_id: connection_id.to_owned(),
+      message_type: "update",
+      object_type: "uir",
+      data: Update {
+        set: None,
+        delete: Some(ObjectsSet {
+          pilots: None,
+          airports: None,
+          firs: None,
+          uirs: Some(data),
+          tracons: None,
         }),
       },
     }
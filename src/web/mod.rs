@@ -1,3 +1,4 @@
+pub mod admin;
 mod calc;
 pub mod error;
 mod filter;
@@ -5,16 +6,17 @@ mod message;
 mod types;
 
 use self::{
-  error::APIError,
+  error::{bad_request, APIError},
   filter::compile_filter,
   message::UpdateMessage,
-  types::{PilotApiResponse, QueryCheckOkResponse},
+  types::{HistoryEntry, PilotApiResponse, QueryCheckOkResponse, TrackApiResponse},
 };
 use crate::{
   fixed::types::Airport,
   lee::{make_expr, parser::expression::CompileFunc},
-  manager::Manager,
+  manager::{health::HealthReport, movements::AirportBoard, Manager},
   moving::pilot::Pilot,
+  persistent::{simplify::simplify, ControllerSession, SessionQuery, TrackQuery},
   seconds_since,
   types::{Point, Rect},
 };
@@ -22,7 +24,11 @@ use chrono::Utc;
 use log::{debug, info};
 use rocket::{
   get,
-  response::stream::{Event, EventStream},
+  http::Status,
+  response::{
+    status::Custom,
+    stream::{Event, EventStream},
+  },
   serde::json::Json,
   Shutdown, State,
 };
@@ -55,10 +61,7 @@ pub async fn updates(
   );
   let mut tm = interval(Duration::from_secs(5));
 
-  let show_wx = match show_wx {
-    Some(value) => value,
-    None => false,
-  };
+  let show_wx = show_wx.unwrap_or_default();
 
   let rect = Rect {
     south_west: Point {
@@ -78,6 +81,8 @@ pub async fn updates(
   let mut pilots_state = HashMap::new();
   let mut airports_state = HashMap::new();
   let mut firs_state = HashMap::new();
+  let mut uirs_state = HashMap::new();
+  let mut tracons_state = HashMap::new();
   let f_expr = {
     if let Some(query) = query {
       let mut expr = make_expr::<Pilot>(query.as_str())?;
@@ -155,6 +160,36 @@ pub async fn updates(
           messages.push(UpdateMessage::firs_set(&client_id, firs_set));
           messages.push(UpdateMessage::firs_delete(&client_id, firs_delete));
 
+          let t = Utc::now();
+          let uirs = if no_bounds {
+            manager.get_all_uirs().await
+          } else {
+            manager.get_uirs(&rect).await
+          };
+
+          debug!("[{}] {} uirs loaded in {}s", client_id, uirs.len(), seconds_since(t));
+          let t = Utc::now();
+          let (uirs_set, uirs_delete) = calc::calc_uirs(&uirs, &mut uirs_state);
+          debug!("[{}] {} uirs diff calculated in {}s, set={}/del={}", client_id, uirs.len(), seconds_since(t), uirs_set.len(), uirs_delete.len());
+
+          messages.push(UpdateMessage::uirs_set(&client_id, uirs_set));
+          messages.push(UpdateMessage::uirs_delete(&client_id, uirs_delete));
+
+          let t = Utc::now();
+          let tracons = if no_bounds {
+            manager.get_all_tracons().await
+          } else {
+            manager.get_tracons(&rect).await
+          };
+
+          debug!("[{}] {} tracons loaded in {}s", client_id, tracons.len(), seconds_since(t));
+          let t = Utc::now();
+          let (tracons_set, tracons_delete) = calc::calc_tracons(&tracons, &mut tracons_state);
+          debug!("[{}] {} tracons diff calculated in {}s, set={}/del={}", client_id, tracons.len(), seconds_since(t), tracons_set.len(), tracons_delete.len());
+
+          messages.push(UpdateMessage::tracons_set(&client_id, tracons_set));
+          messages.push(UpdateMessage::tracons_delete(&client_id, tracons_delete));
+
           messages
         }
       };
@@ -177,14 +212,32 @@ pub async fn get_airport(code: String, manager: &State<Arc<Manager>>) -> Option<
   manager.find_airport(&code).await.map(Json)
 }
 
-#[get("/pilots/<callsign>")]
+// recent takeoffs and landings plus inbound and outbound pilots
+#[get("/airports/<code>/board")]
+pub async fn get_airport_board(
+  code: String,
+  manager: &State<Arc<Manager>>,
+) -> Option<Json<AirportBoard>> {
+  manager.get_airport_board(&code).await.map(Json)
+}
+
+// max_points limits the number of track points returned by simplifying the track,
+// since (unix timestamp in ms) makes it possible to fetch only the points added
+// after the last request
+#[get("/pilots/<callsign>?<max_points>&<since>")]
 pub async fn get_pilot(
   callsign: String,
+  max_points: Option<usize>,
+  since: Option<i64>,
   manager: &State<Arc<Manager>>,
 ) -> Result<Option<Json<PilotApiResponse>>, APIError> {
   let pilot = manager.get_pilot_by_callsign(&callsign).await;
   if let Some(pilot) = pilot {
-    let tps = manager.get_pilot_track(&pilot).await?;
+    let tps = manager.get_pilot_track(&pilot, since).await?;
+    let tps = match max_points {
+      Some(max_points) => tps.map(|tps| simplify(tps, max_points)),
+      None => tps,
+    };
     let mut resp: PilotApiResponse = pilot.into();
     resp.track = tps;
     Ok(Some(Json(resp)))
@@ -193,6 +246,68 @@ pub async fn get_pilot(
   }
 }
 
+// past flights are available within the track retention window,
+// the most recent ones go first
+#[get("/history/cid/<cid>")]
+pub async fn get_history_by_cid(
+  cid: u32,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<Vec<HistoryEntry>>, APIError> {
+  let tracks = manager.find_tracks(&TrackQuery::Cid(cid)).await?;
+  Ok(Json(tracks.into_iter().map(|info| info.into()).collect()))
+}
+
+#[get("/history/callsign/<callsign>")]
+pub async fn get_history_by_callsign(
+  callsign: String,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<Vec<HistoryEntry>>, APIError> {
+  let query = TrackQuery::Callsign(callsign.to_uppercase());
+  let tracks = manager.find_tracks(&query).await?;
+  Ok(Json(tracks.into_iter().map(|info| info.into()).collect()))
+}
+
+#[get("/tracks/<code>?<max_points>")]
+pub async fn get_track(
+  code: String,
+  max_points: Option<usize>,
+  manager: &State<Arc<Manager>>,
+) -> Result<Option<Json<TrackApiResponse>>, APIError> {
+  let info = manager.get_track_info(&code).await?;
+  if let Some(info) = info {
+    let tps = manager
+      .get_track_points(&code, None)
+      .await?
+      .unwrap_or_default();
+    let track = match max_points {
+      Some(max_points) => simplify(tps, max_points),
+      None => tps,
+    };
+    Ok(Some(Json(TrackApiResponse { info, track })))
+  } else {
+    Ok(None)
+  }
+}
+
+// controller sessions within the track retention window, most recent first.
+// Airport sessions are attached to the airport's FIR as well
+#[get("/controllers/history?<airport>&<fir>")]
+pub async fn get_controllers_history(
+  airport: Option<String>,
+  fir: Option<String>,
+  manager: &State<Arc<Manager>>,
+) -> Result<Json<Vec<ControllerSession>>, APIError> {
+  if airport.is_none() && fir.is_none() {
+    return Err(bad_request("either airport or fir is required"));
+  }
+  let query = SessionQuery {
+    airport: airport.map(|code| code.to_uppercase()),
+    fir: fir.map(|code| code.to_uppercase()),
+  };
+  let sessions = manager.find_controller_sessions(&query).await?;
+  Ok(Json(sessions))
+}
+
 #[get("/chkquery?<query>")]
 pub async fn check_query(query: String) -> Result<Json<QueryCheckOkResponse>, APIError> {
   let mut expr = make_expr::<Pilot>(query.as_str())?;
@@ -227,3 +342,20 @@ pub async fn build_info() -> Json<BuildInfo> {
 pub async fn metrics(manager: &State<Arc<Manager>>) -> String {
   manager.render_metrics().await
 }
+
+// liveness, the breakdown is informational and the status is always 200
+#[get("/healthz")]
+pub async fn healthz(manager: &State<Arc<Manager>>) -> Json<HealthReport> {
+  Json(manager.health().await)
+}
+
+#[get("/readyz")]
+pub async fn readyz(manager: &State<Arc<Manager>>) -> Custom<Json<HealthReport>> {
+  let report = manager.health().await;
+  let status = if report.ready {
+    Status::Ok
+  } else {
+    Status::ServiceUnavailable
+  };
+  Custom(status, Json(report))
+}
@@ -4,9 +4,9 @@ use serde::Serialize;
 use crate::{
   moving::{
     aircraft::Aircraft,
-    pilot::{FlightPlan, Pilot},
+    pilot::{FlightPhase, FlightPlan, FlightProgress, Pilot},
   },
-  persistent::TrackPoint,
+  persistent::{TrackInfo, TrackPoint},
   types::Point,
 };
 
@@ -29,6 +29,10 @@ pub struct PilotApiResponse {
   pub last_updated: DateTime<Utc>,
   pub track: Option<Vec<TrackPoint>>,
   pub aircraft_type: Option<Vec<&'static Aircraft>>,
+  pub fir: Option<String>,
+  pub controlled_by: Option<String>,
+  pub phase: Option<FlightPhase>,
+  pub progress: Option<FlightProgress>,
 }
 
 impl From<Pilot> for PilotApiResponse {
@@ -51,6 +55,10 @@ impl From<Pilot> for PilotApiResponse {
       last_updated: p.last_updated,
       track: None,
       aircraft_type: p.aircraft_type,
+      fir: p.fir,
+      controlled_by: p.controlled_by,
+      phase: p.phase,
+      progress: p.progress,
     }
   }
 }
@@ -59,3 +67,24 @@ impl From<Pilot> for PilotApiResponse {
 pub struct QueryCheckOkResponse {
   pub status: &'static str,
 }
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+  #[serde(flatten)]
+  pub info: TrackInfo,
+  pub track_url: String,
+}
+
+impl From<TrackInfo> for HistoryEntry {
+  fn from(info: TrackInfo) -> Self {
+    let track_url = format!("/api/tracks/{}", info.code);
+    Self { info, track_url }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackApiResponse {
+  #[serde(flatten)]
+  pub info: TrackInfo,
+  pub track: Vec<TrackPoint>,
+}